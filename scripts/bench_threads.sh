@@ -0,0 +1,23 @@
+#!/bin/sh
+# Measure how solve scales with --num-threads.
+# usage: scripts/bench_threads.sh [answers] [threads...]
+# e.g.   scripts/bench_threads.sh DP 1 2 4 8
+set -eu
+
+answers=${1:-GS}
+[ $# -gt 0 ] && shift
+threads=${*:-1 2 4 8}
+
+cargo build --release --bin solve >/dev/null 2>&1
+out=$(mktemp)
+trap 'rm -f "$out"' EXIT
+
+echo "| threads | elapsed [sec] | speedup |"
+echo "|--------:|--------------:|--------:|"
+base=
+for t in $threads; do
+    elapsed=$(./target/release/solve --answers "$answers" -t "$t" -o "$out" |
+        sed -n 's/^elapsed time: \([0-9.]*\) \[sec\]$/\1/p')
+    base=${base:-$elapsed}
+    echo "$t $elapsed $base" | awk '{ printf "| %7d | %13.2f | %7.2f |\n", $1, $2, $3 / $2 }'
+done
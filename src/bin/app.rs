@@ -1,38 +1,253 @@
+use std::collections::HashMap;
 use std::io::Write;
+use std::rc::Rc;
 
 use argh::FromArgs;
 
-use wordle_pokemon::{judge::*, pokemon::*, tree::*};
+use wordle_pokemon::{cli::*, judge::*, live::*, pokemon::*, tree::*};
 
 #[derive(FromArgs)]
 /// Build decision tree
 struct Args {
-    /// the filepath of decision tree input
+    /// the filepath of decision tree input, .txt or .json (not needed with --live)
     #[argh(option, short = 'i')]
-    input: String,
+    input: Option<String>,
+
+    /// suggest guesses on the fly from the guesses actually played
+    #[argh(switch)]
+    live: bool,
+
+    /// suggest only guesses consistent with the judges seen so far (with --live)
+    #[argh(switch)]
+    hard: bool,
+
+    /// the number of characters of answer pokemons (with --live)
+    #[argh(option, default = "WORD_LEN")]
+    word_len: usize,
+
+    /// the filepath of word list (defaults to the built-in pokemons)
+    #[argh(option)]
+    words: Option<String>,
+
+    /// the last generation of answer pokemons, e.g. BW (defaults to the tree)
+    #[argh(option)]
+    answers: Option<String>,
+
+    /// the last generation of guess pokemons, e.g. SWSH (defaults to the tree)
+    #[argh(option)]
+    guesses: Option<String>,
+
+    /// the filepath of answers to use, one name per line, e.g. those the tree was solved with
+    #[argh(option)]
+    allow_answers: Option<String>,
+
+    /// the filepath of answers not to use, one name per line, e.g. those the tree was solved without
+    #[argh(option)]
+    deny_answers: Option<String>,
+}
+
+fn read_line() -> Option<String> {
+    print!("-> ");
+    std::io::stdout().flush().unwrap();
+    let mut s = String::new();
+    if std::io::stdin().read_line(&mut s).unwrap() == 0 {
+        return None;
+    }
+    Some(s.trim().to_string())
+}
+
+fn print_node(node: &Node, pokemons: &PokemonList) {
+    match node {
+        Node::NonTerminal { guess, rem_ans, .. } => {
+            println!("(残り{}匹) {}", rem_ans.len(), pokemons.names[*guess]);
+        }
+        Node::Terminal => {
+            println!("Congratulations!!!");
+            println!("If you want to play again, type \"restart\".");
+        }
+    }
+}
+
+fn print_valid_judges(node: &Node, pokemons: &PokemonList) {
+    let judges = node
+        .judges()
+        .iter()
+        .map(|judge| {
+            Feedback::from_judge(*judge, pokemons.word_len)
+                .expect("judges in the tree come from the judge table")
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    println!("Valid responses: {}", judges);
 }
 
 fn main() {
     let args: Args = argh::from_env();
 
-    let tree = DecisionTree::new(&args.input);
-    let pokemons = PokemonList::new(tree.judge_table.ans_until, tree.judge_table.guess_until);
-    let mut node = tree.build(&pokemons.all_ans, 0);
-
-    while let Node::NonTerminal { guess, rem_ans, .. } = &*node {
-        println!("(残り{}匹) {}", rem_ans.len(), POKEMONS[*guess]);
-
-        print!("-> ");
-        std::io::stdout().flush().unwrap();
-        let mut s = String::new();
-        std::io::stdin().read_line(&mut s).unwrap();
-        let s = s.trim().to_string();
-
-        node = node.next(
-            &(s.chars()
-                .enumerate()
-                .map(|(i, c)| c.to_digit(10).unwrap() << 2 * i)
-                .sum::<u32>() as Judge),
-        );
+    let words = or_exit(WordList::from_arg(args.words.as_deref()));
+
+    if args.live {
+        return live_mode(&args, &words);
+    }
+    match &args.input {
+        Some(input) => tree_mode(&args, input, &words),
+        None => {
+            eprintln!("error: either --input or --live is required");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn tree_mode(args: &Args, input: &str, words: &WordList) {
+    let (tree, root) = or_exit(DecisionTree::load_root(
+        input,
+        words,
+        or_exit(words.until_arg(args.answers.as_deref())),
+        or_exit(words.until_arg(args.guesses.as_deref())),
+        &or_exit(WordFilter::load(
+            words,
+            args.allow_answers.as_deref(),
+            args.deny_answers.as_deref(),
+        )),
+    ));
+
+    println!("Type the response (e.g. 02110 or ⬛🟩🟨🟨⬛), \"undo\", \"restart\" or \"quit\".");
+
+    // 根から現在の節点までの経路.
+    let mut path: Vec<Rc<Node>> = vec![Rc::clone(&root)];
+    print_node(&root, &tree.pokemons);
+
+    while let Some(s) = read_line() {
+        match s.as_str() {
+            "quit" | "exit" => break,
+            "restart" => {
+                path.truncate(1);
+            }
+            "undo" => {
+                if path.len() == 1 {
+                    println!("Nothing to undo.");
+                    continue;
+                }
+                path.pop();
+            }
+            _ => {
+                let node = Rc::clone(path.last().unwrap());
+                if let Node::Terminal = *node {
+                    println!("Already solved. Type \"undo\" or \"restart\".");
+                    continue;
+                }
+                let next = Feedback::parse(&s, tree.pokemons.word_len)
+                    .map_err(|e| e.to_string())
+                    .and_then(|feedback| {
+                        node.next(&feedback.to_judge())
+                            .ok_or_else(|| format!("{} is not possible here", feedback))
+                    });
+                match next {
+                    Ok(next) => path.push(next),
+                    Err(e) => {
+                        println!("Incorrect input: {}.", e);
+                        print_valid_judges(&node, &tree.pokemons);
+                        continue;
+                    }
+                }
+            }
+        }
+        print_node(path.last().unwrap(), &tree.pokemons);
+    }
+}
+
+fn live_mode(args: &Args, words: &WordList) {
+    let mut pokemons = or_exit(PokemonList::from_words(
+        words,
+        args.word_len,
+        or_exit(words.until_arg(args.answers.as_deref())).unwrap_or(words.len()),
+        or_exit(words.until_arg(args.guesses.as_deref())).unwrap_or(words.len()),
+    ));
+    // 残りの答えが少ないときは答えから宣言するので, 答えは全て宣言できなければならない.
+    let answers = or_exit(WordFilter::load(
+        words,
+        args.allow_answers.as_deref(),
+        args.deny_answers.as_deref(),
+    ));
+    or_exit(pokemons.restrict(words, &answers, &WordFilter::default()));
+    let judge_table = JudgeTable::new(&pokemons);
+    let index: HashMap<&str, Guess> = pokemons
+        .all_guess
+        .iter()
+        .map(|guess| (pokemons.names[*guess].as_str(), *guess))
+        .collect();
+
+    let mut solver = LiveSolver::new(words, &pokemons, &judge_table, args.hard);
+
+    println!(
+        "Type the guess you played and the response (e.g. ランクルス 02110), \"undo\", \"restart\" or \"quit\"."
+    );
+    print_suggestion(&solver, &pokemons);
+
+    while let Some(s) = read_line() {
+        match s.as_str() {
+            "quit" | "exit" => break,
+            "restart" => solver.restart(),
+            "undo" => {
+                if !solver.undo() {
+                    println!("Nothing to undo.");
+                    continue;
+                }
+            }
+            _ => {
+                let mut cols = s.split_whitespace();
+                let (name, response) = match (cols.next(), cols.next(), cols.next()) {
+                    (Some(name), Some(response), None) => (name, response),
+                    _ => {
+                        println!("Incorrect input: expected a guess and a response.");
+                        continue;
+                    }
+                };
+                let guess = match index.get(name) {
+                    Some(guess) => *guess,
+                    None => {
+                        println!("Incorrect input: {} cannot be guessed.", name);
+                        continue;
+                    }
+                };
+                if args.hard && !solver.guesses().contains(&guess) {
+                    println!("Incorrect input: {} is not allowed in hard mode.", name);
+                    continue;
+                }
+                match Feedback::parse(response, pokemons.word_len) {
+                    Ok(feedback) => solver.play(guess, feedback.to_judge()),
+                    Err(e) => {
+                        println!("Incorrect input: {}.", e);
+                        continue;
+                    }
+                }
+            }
+        }
+        print_suggestion(&solver, &pokemons);
+    }
+}
+
+fn print_suggestion(solver: &LiveSolver, pokemons: &PokemonList) {
+    let rem_ans = &solver.rem_ans;
+    match rem_ans.len() {
+        0 => println!("No pokemon matches. Type \"undo\" to fix the last input."),
+        1 => println!("答え: {}", pokemons.names[rem_ans[0]]),
+        n => {
+            if n <= EXACT_LIMIT {
+                let names: Vec<&str> = rem_ans
+                    .iter()
+                    .map(|ans| pokemons.names[*ans].as_str())
+                    .collect();
+                println!("候補: {}", names.join(" "));
+            }
+            let guess = or_exit(solver.suggest()).unwrap();
+            println!(
+                "(残り{}匹) {}{}",
+                n,
+                pokemons.names[guess],
+                if solver.is_exact() { "" } else { " (貪欲)" }
+            );
+        }
     }
 }
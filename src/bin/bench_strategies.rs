@@ -0,0 +1,172 @@
+use argh::FromArgs;
+use std::fs;
+use std::time::Instant;
+
+use wordle_pokemon::{cli::*, heuristic::*, pokemon::*, solver::*};
+
+#[derive(FromArgs)]
+/// Compare greedy trees of each heuristic with the known optimal trees
+struct Args {
+    /// the last generation of answer pokemons to compare, e.g. --pool DP --pool BW (defaults to all solved pools)
+    #[argh(option)]
+    pool: Vec<String>,
+
+    /// the heuristics to compare, e.g. --heuristic entropy --heuristic weight:0.2 (defaults to all built-in heuristics)
+    #[argh(option)]
+    heuristic: Vec<Heuristic>,
+
+    /// the filepath of CSV output
+    #[argh(option)]
+    csv: Option<String>,
+
+    /// the number of threads
+    #[argh(option, short = 't', default = "1")]
+    num_threads: usize,
+}
+
+// README の最適解 (合計, 最悪回数).
+const OPTIMAL: [(&str, usize, usize); 5] = [
+    ("DP", 942, 6),
+    ("BW", 1328, 6),
+    ("XY", 1512, 6),
+    ("SM", 1713, 7),
+    ("SWSH", 1859, 6),
+];
+
+const HEURISTICS: [&str; 5] = [
+    "entropy",
+    "expected-size",
+    "max-bucket",
+    "num-buckets",
+    "weight:0.1",
+];
+
+struct Row {
+    pool: String,
+    heuristic: String,
+    n: usize,
+    total: usize,
+    worst: usize,
+    optimal: Option<(usize, usize)>,
+    elapsed: f64,
+}
+
+impl Row {
+    fn gap(&self) -> Option<f64> {
+        self.optimal
+            .map(|(total, _)| 100.0 * (self.total as f64 - total as f64) / total as f64)
+    }
+}
+
+fn main() {
+    let args: Args = argh::from_env();
+
+    let words = WordList::default();
+    let pools: Vec<String> = if args.pool.is_empty() {
+        OPTIMAL.iter().map(|(pool, ..)| pool.to_string()).collect()
+    } else {
+        args.pool.clone()
+    };
+    let pools: Vec<(String, usize)> = pools
+        .into_iter()
+        .map(|pool| {
+            let until = or_exit(words.until(&pool));
+            (pool, until)
+        })
+        .collect();
+    let heuristics: Vec<Heuristic> = if args.heuristic.is_empty() {
+        HEURISTICS.iter().map(|h| h.parse().unwrap()).collect()
+    } else {
+        args.heuristic.clone()
+    };
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.num_threads)
+        .build()
+        .unwrap();
+
+    println!("| pool (n_ans) | heuristic | greedy average score | worst case | optimal average score | optimal worst case | gap | time[s] |");
+    println!("|--------------|-----------|---------------------:|-----------:|----------------------:|-------------------:|----:|--------:|");
+    let mut rows = vec![];
+    for (generation, ans_until) in &pools {
+        for heuristic in &heuristics {
+            let solver = or_exit(
+                SolverBuilder::new()
+                    .ans_until(*ans_until)
+                    .greedy_heuristic(heuristic.clone())
+                    .build(&words),
+            );
+
+            let start = Instant::now();
+            pool.install(|| solver.solve_greedy());
+            let elapsed = start.elapsed().as_secs_f64();
+            let root = solver.build_node();
+
+            let row = Row {
+                pool: generation.clone(),
+                heuristic: heuristic.name.clone(),
+                n: solver.num_answers(),
+                total: root.total(),
+                worst: root.worst(),
+                optimal: OPTIMAL
+                    .iter()
+                    .find(|(g, ..)| g == generation)
+                    .map(|(_, total, worst)| (*total, *worst)),
+                elapsed,
+            };
+            let (optimal_average, optimal_worst, gap) = match (row.optimal, row.gap()) {
+                (Some((total, worst)), Some(gap)) => (
+                    format!("{:.4} (= {}/{})", total as f64 / row.n as f64, total, row.n),
+                    worst.to_string(),
+                    format!("{:.2}%", gap),
+                ),
+                _ => ("-".to_string(), "-".to_string(), "-".to_string()),
+            };
+            println!(
+                "| {} ({}) | {} | {:.4} (= {}/{}) | {} | {} | {} | {} | {:.2} |",
+                row.pool,
+                row.n,
+                row.heuristic,
+                row.total as f64 / row.n as f64,
+                row.total,
+                row.n,
+                row.worst,
+                optimal_average,
+                optimal_worst,
+                gap,
+                row.elapsed
+            );
+            rows.push(row);
+        }
+    }
+
+    if let Some(filepath) = &args.csv {
+        let mut csv = String::from(
+            "pool,n_ans,heuristic,total,average,worst,optimal_total,optimal_worst,gap_percent,elapsed_sec\n",
+        );
+        for row in &rows {
+            let (optimal_total, optimal_worst) = row
+                .optimal
+                .map_or((String::new(), String::new()), |(total, worst)| {
+                    (total.to_string(), worst.to_string())
+                });
+            csv += &format!(
+                "{},{},{},{},{:.4},{},{},{},{},{:.2}\n",
+                row.pool,
+                row.n,
+                row.heuristic,
+                row.total,
+                row.total as f64 / row.n as f64,
+                row.worst,
+                optimal_total,
+                optimal_worst,
+                row.gap().map_or(String::new(), |gap| format!("{:.2}", gap)),
+                row.elapsed
+            );
+        }
+        if let Err(e) = fs::write(filepath, csv) {
+            eprintln!("error: {}: {}", filepath, e);
+            std::process::exit(1);
+        }
+    }
+}
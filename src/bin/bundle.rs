@@ -0,0 +1,84 @@
+use argh::FromArgs;
+use std::fs;
+
+use wordle_pokemon::{bundle::*, cli::*, pokemon::*, tree::*};
+
+#[derive(FromArgs)]
+/// Bundle decision trees for the web app
+struct Args {
+    /// the trees to bundle as MODE=FILE, e.g. DP=outputs/opt_DP.txt (.txt or .json)
+    #[argh(positional)]
+    trees: Vec<String>,
+
+    /// the filepath of bundle output, e.g. docs/js/const.js (plain JSON if it ends with .json)
+    #[argh(option, short = 'o')]
+    output: String,
+
+    /// the filepath of word list (defaults to the built-in pokemons)
+    #[argh(option)]
+    words: Option<String>,
+
+    /// the filepath of answers the tree was solved with, one name per line (only these answers within --answers)
+    #[argh(option)]
+    allow_answers: Option<String>,
+
+    /// the filepath of answers the tree was solved without, one name per line
+    #[argh(option)]
+    deny_answers: Option<String>,
+
+    /// include the remaining answer names in each node
+    #[argh(switch)]
+    with_answers: bool,
+
+    /// include the expected number of guesses in each node
+    #[argh(switch)]
+    with_score: bool,
+}
+
+fn main() {
+    let args: Args = argh::from_env();
+
+    let words = or_exit(WordList::from_arg(args.words.as_deref()));
+    let answers = or_exit(WordFilter::load(
+        &words,
+        args.allow_answers.as_deref(),
+        args.deny_answers.as_deref(),
+    ));
+    let options = JsonOptions {
+        answers: args.with_answers,
+        score: args.with_score,
+    };
+
+    let entries: Vec<(String, String)> = args
+        .trees
+        .iter()
+        .map(|tree| {
+            let (label, input) = match tree.split_once('=') {
+                Some(pair) => pair,
+                None => {
+                    eprintln!("error: {}: expected MODE=FILE", tree);
+                    std::process::exit(1);
+                }
+            };
+            let (tree, root) =
+                or_exit(DecisionTree::load_root(input, &words, None, None, &answers));
+            let json = serde_json::to_string(&root.to_json(&tree.pokemons, &options)).unwrap();
+            (label.to_string(), json)
+        })
+        .collect();
+
+    let written = if args.output.ends_with(".json") {
+        let body = entries
+            .iter()
+            .map(|(label, json)| format!("{}:{}", serde_json::to_string(label).unwrap(), json))
+            .collect::<Vec<String>>()
+            .join(",");
+        fs::write(&args.output, format!("{{{}}}", body))
+    } else {
+        write_const_js(&args.output, &entries)
+    };
+    if let Err(e) = written {
+        eprintln!("error: {}: {}", args.output, e);
+        std::process::exit(1);
+    }
+}
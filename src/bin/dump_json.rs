@@ -1,29 +1,80 @@
 use argh::FromArgs;
 use std::fs;
 
-use wordle_pokemon::{pokemon::*, tree::*};
+use wordle_pokemon::{cli::*, pokemon::*, tree::*};
 
 #[derive(FromArgs)]
 /// Build decision tree
 struct Args {
-    /// the filepath of decision tree input
+    /// the filepath of decision tree input, .txt or .json
     #[argh(option, short = 'i')]
     input: String,
 
-    /// the filepath of decision tree json output
+    /// the filepath of decision tree json output (compact binary if it ends with .bin)
     #[argh(option, short = 'o')]
     output: String,
+
+    /// the filepath of word list (defaults to the built-in pokemons)
+    #[argh(option)]
+    words: Option<String>,
+
+    /// the last generation of answer pokemons, e.g. BW (defaults to the tree)
+    #[argh(option)]
+    answers: Option<String>,
+
+    /// the last generation of guess pokemons, e.g. SWSH (defaults to the tree)
+    #[argh(option)]
+    guesses: Option<String>,
+
+    /// the filepath of answers the tree was solved with, one name per line (only these answers within --answers)
+    #[argh(option)]
+    allow_answers: Option<String>,
+
+    /// the filepath of answers the tree was solved without, one name per line
+    #[argh(option)]
+    deny_answers: Option<String>,
+
+    /// include the remaining answer names in each node
+    #[argh(switch)]
+    with_answers: bool,
+
+    /// include the expected number of guesses in each node
+    #[argh(switch)]
+    with_score: bool,
 }
 
 fn main() {
     let args: Args = argh::from_env();
 
-    let tree = DecisionTree::new(&args.input);
-    let pokemons = PokemonList::new(tree.judge_table.ans_until, tree.judge_table.guess_until);
-    let root = tree.build(&pokemons.all_ans, 0);
+    let words = or_exit(WordList::from_arg(args.words.as_deref()));
+    let (tree, root) = or_exit(DecisionTree::load_root(
+        &args.input,
+        &words,
+        or_exit(words.until_arg(args.answers.as_deref())),
+        or_exit(words.until_arg(args.guesses.as_deref())),
+        &or_exit(WordFilter::load(
+            &words,
+            args.allow_answers.as_deref(),
+            args.deny_answers.as_deref(),
+        )),
+    ));
 
-    let mut f = fs::File::create(&args.output).unwrap();
-    root.write(&mut f);
+    if args.output.ends_with(".bin") {
+        // バイナリから読み戻した木が JSON で書き出す木と一致するか確かめてから書き出す.
+        let json = root.to_json(&tree.pokemons, &JsonOptions::default());
+        let bytes = json.to_binary().unwrap();
+        if JsonNode::from_binary(&bytes).ok().as_ref() != Some(&json) {
+            eprintln!("error: the binary tree does not round-trip");
+            std::process::exit(1);
+        }
+        fs::write(&args.output, bytes).unwrap();
+        return;
+    }
 
-    return;
+    let mut f = fs::File::create(&args.output).unwrap();
+    let options = JsonOptions {
+        answers: args.with_answers,
+        score: args.with_score,
+    };
+    root.write(&mut f, &tree.pokemons, &options);
 }
@@ -1,357 +1,124 @@
 use argh::FromArgs;
-use ordered_float::OrderedFloat;
-use rayon::prelude::*;
-use std::collections::HashMap;
-use std::fs;
-use std::io::Write;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 //use pprof::protos::Message;
 
-use wordle_pokemon::{judge::*, pokemon::*};
+use wordle_pokemon::{cli::*, heuristic::*, pokemon::*, solver::*};
 
-type SetId = usize;
-type Score = i32;
-
-const INFTY: Score = Score::MAX / 2;
-
-#[derive(Default)]
-struct Cache {
-    memo: HashMap<SetId, (Score, Guess, Partition)>,
-    best: HashMap<SetId, Score>,
-    lb_memo: HashMap<SetId, (usize, Score)>,
-
-    set_id: HashMap<Vec<Answer>, SetId>,
-    cnt: usize,
-}
-impl Cache {
-    pub fn get_set_id(&mut self, st: &Vec<Answer>) -> SetId {
-        if let Some(id) = self.set_id.get(st) {
-            return *id;
-        }
-        let id = self.cnt;
-        self.cnt += 1;
-        self.set_id.insert(st.clone(), id);
-
-        return id;
-    }
-}
-
-#[derive(Default)]
-struct Solver {
-    ans_until: usize,
-    //guess_until: usize,
-    pokemons: PokemonList,
-    judge_table: JudgeTable,
-
-    cache: Arc<Mutex<Cache>>,
-}
-
-impl Solver {
-    const LB_DEPTH_LIMIT: usize = 1;
-    pub fn new(ans_until: usize, guess_until: usize) -> Self {
-        let pokemons = PokemonList::new(ans_until, guess_until);
-        let judge_table = JudgeTable::new(ans_until, guess_until);
-
-        Self {
-            ans_until,
-            //guess_until,
-            pokemons,
-            judge_table,
-            ..Default::default()
-        }
-    }
-
-    #[allow(dead_code)]
-    pub fn build_good_solution(&self) {
-        println!(
-            "期待回数(貪欲): {} = {}/{}",
-            self.dfs_good_solution(&self.pokemons.all_ans) as f32
-                / self.pokemons.all_ans.len() as f32,
-            self.dfs_good_solution(&self.pokemons.all_ans),
-            self.pokemons.all_ans.len()
-        );
-    }
-
-    fn dfs_good_solution(&self, rem_ans: &Vec<Answer>) -> Score {
-        assert!(rem_ans.len() > 0);
-        if rem_ans.len() == 1 {
-            return 1;
-        }
-        if rem_ans.len() == 2 {
-            return 1 + 2;
-        }
-
-        let rem_id = self.cache.lock().unwrap().get_set_id(rem_ans);
-
-        if let Some((val, ..)) = self.cache.lock().unwrap().memo.get(&rem_id) {
-            return *val;
-        }
-
-        let all_guess = if rem_ans.len() == 3 {
-            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
-            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+#[derive(FromArgs)]
+/// Minimize expectation of the number of guess
+struct Args {
+    /// the number of answer pokemons (defaults to the whole word list)
+    #[argh(option)]
+    ans_until: Option<usize>,
 
-            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
-            rem_ans
-        } else {
-            &self.pokemons.all_guess
-        };
-
-        let good_guess = all_guess
-            .par_iter()
-            .min_by_key(|guess| {
-                OrderedFloat(
-                    self.judge_table
-                        .partition(rem_ans, &guess)
-                        .values()
-                        .map(|s| {
-                            // minimize average size, maximize entropy
-                            let x = s.len() as f32;
-                            (0.1 * x + x.log2()) * x
-                        })
-                        .sum::<f32>(),
-                )
-            })
-            .unwrap();
-
-        // TODO: avoid same calculation
-        let part = self.judge_table.partition(rem_ans, &good_guess);
-
-        let val: Score = rem_ans.len() as Score
-            + part
-                .par_iter()
-                .map(|(_, s)| self.dfs_good_solution(s))
-                .sum::<Score>();
-
-        self.cache
-            .lock()
-            .unwrap()
-            .memo
-            .insert(rem_id, (val, *good_guess, part.clone()));
-
-        val
-    }
+    /// the number of guess pokemons (defaults to the whole word list)
+    #[argh(option)]
+    guess_until: Option<usize>,
 
-    fn lower_bound(&self, rem_ans: &Vec<Answer>, depth: usize) -> Score {
-        assert!(rem_ans.len() > 0);
-        if depth == 0 || rem_ans.len() <= 2 {
-            return 2 * rem_ans.len() as Score - 1;
-        }
+    /// the last generation of answer pokemons, e.g. BW (instead of --ans-until)
+    #[argh(option)]
+    answers: Option<String>,
 
-        let rem_id = self.cache.lock().unwrap().get_set_id(rem_ans);
+    /// the last generation of guess pokemons, e.g. SWSH (instead of --guess-until)
+    #[argh(option)]
+    guesses: Option<String>,
 
-        if let Some((d, lb)) = self.cache.lock().unwrap().lb_memo.get(&rem_id) {
-            if *d >= depth {
-                return *lb;
-            }
-        }
+    /// the filepath of word list (defaults to the built-in pokemons)
+    #[argh(option)]
+    words: Option<String>,
 
-        let all_guess = if rem_ans.len() == 3 {
-            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
-            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+    /// the number of characters of answer pokemons
+    #[argh(option, default = "WORD_LEN")]
+    word_len: usize,
 
-            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
-            rem_ans
-        } else {
-            &self.pokemons.all_guess
-        };
-
-        let ret: Score = rem_ans.len() as Score
-            + all_guess
-                .par_iter()
-                .map(|guess| {
-                    self.judge_table
-                        .partition(rem_ans, &guess)
-                        .values()
-                        .map(|s| self.lower_bound(s, depth - 1))
-                        .sum::<Score>()
-                })
-                .min()
-                .unwrap();
-
-        // assert!(ret >= 2 * rem_ans.len() as Score - 1);
-
-        self.cache
-            .lock()
-            .unwrap()
-            .lb_memo
-            .insert(rem_id, (depth, ret));
-        ret
-    }
+    /// the objective to minimize: average (default) or worst, which
+    /// minimizes the worst case first and breaks ties by average
+    #[argh(option, default = "Objective::default()")]
+    objective: Objective,
 
-    pub fn build_best_solution(&self) {
-        println!(
-            "期待回数(最適): {} = {}/{}",
-            self.dfs_best_solution(&self.pokemons.all_ans, INFTY) as f32
-                / self.pokemons.all_ans.len() as f32,
-            self.dfs_best_solution(&self.pokemons.all_ans, INFTY),
-            self.pokemons.all_ans.len()
-        );
-    }
+    /// restrict every guess to be consistent with the judges seen so far
+    #[argh(switch)]
+    hard: bool,
 
-    fn dfs_best_solution(&self, rem_ans: &Vec<Answer>, ub: Score) -> Score {
-        assert!(rem_ans.len() > 0);
-        if rem_ans.len() == 1 {
-            return 1;
-        }
-        if rem_ans.len() == 2 {
-            return 1 + 2;
-        }
+    /// the number of guesses to look ahead in lower bounds
+    #[argh(option, default = "1")]
+    lb_depth: usize,
 
-        let rem_id = self.cache.lock().unwrap().get_set_id(rem_ans);
+    /// the filepath of answer weights, each line a name and a positive integer weight
+    /// (unlisted answers weigh 1), to minimize the weighted expectation
+    #[argh(option)]
+    weights: Option<String>,
 
-        if let Some(val) = self.cache.lock().unwrap().best.get(&rem_id) {
-            return *val;
-        }
+    /// the filepath of answers to use, one name per line (only these answers within --answers)
+    #[argh(option)]
+    allow_answers: Option<String>,
 
-        if self.lower_bound(rem_ans, Self::LB_DEPTH_LIMIT) >= ub {
-            return INFTY;
-        }
+    /// the filepath of answers not to use, one name per line
+    #[argh(option)]
+    deny_answers: Option<String>,
 
-        let mut val = self.dfs_good_solution(rem_ans);
+    /// the filepath of guesses to use, one name per line (only these guesses within --guesses)
+    #[argh(option)]
+    allow_guesses: Option<String>,
 
-        let all_guess = if rem_ans.len() == 3 {
-            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
-            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+    /// the filepath of guesses not to use, one name per line
+    #[argh(option)]
+    deny_guesses: Option<String>,
 
-            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
-            rem_ans
-        } else {
-            &self.pokemons.all_guess
-        };
-
-        let partitions: Vec<Partition> = all_guess
-            .par_iter()
-            .map(|guess| self.judge_table.partition(rem_ans, &guess))
-            .collect();
-
-        let penalty: Vec<f32> = partitions
-            .par_iter()
-            .map(|part| {
-                // maximize "entropy"
-                part.values()
-                    .map(|s| {
-                        let x = s.len() as f32;
-                        x.log2() * x
-                    })
-                    .sum::<f32>()
-            })
-            .collect();
-
-        let mut order: Vec<usize> = (0..all_guess.len()).collect();
-        order.sort_by_key(|i| OrderedFloat(penalty[*i]));
-
-        for &i in order.iter() {
-            let guess = &all_guess[i];
-            let part = &partitions[i];
-
-            let lb = rem_ans.len() as Score
-                + part
-                    .values()
-                    .map(|s| self.lower_bound(s, Self::LB_DEPTH_LIMIT))
-                    .sum::<Score>();
-
-            // // ここを並列化すると遅くなる.
-            // // parallel
-            // let lb: Score = rem_ans.len() as Score + part.par_iter().map(|(_, s)| {
-            //     self.lower_bound(s, Self::LB_DEPTH_LIMIT)
-            // }).sum::<Score>();
-
-            if lb >= val {
-                continue;
-            }
+    /// the heuristic to pick greedy guesses and to order guesses in the search:
+    /// entropy, expected-size, max-bucket, num-buckets or weight:<w> (defaults to weight:0.1 and entropy)
+    #[argh(option)]
+    heuristic: Option<Heuristic>,
 
-            let mut tmp = rem_ans.len() as Score;
-            for s in part.values() {
-                tmp += self.dfs_best_solution(s, val - tmp);
-                if tmp >= val {
-                    break;
-                }
-            }
+    /// the heuristic to order guesses in the search (defaults to --heuristic)
+    #[argh(option)]
+    order_heuristic: Option<Heuristic>,
 
-            if tmp < val {
-                val = tmp;
-                self.cache
-                    .lock()
-                    .unwrap()
-                    .memo
-                    .insert(rem_id, (val, *guess, part.clone()));
-            }
-        }
+    /// stop searching after this many seconds and output the best tree found so far
+    #[argh(option)]
+    time_limit: Option<f64>,
 
-        self.cache.lock().unwrap().best.insert(rem_id, val);
+    /// stop searching after expanding this many states and output the best tree found so far
+    #[argh(option)]
+    node_limit: Option<usize>,
 
-        val
-    }
+    /// the interval of printing progress in seconds (0 disables it)
+    #[argh(option, default = "60")]
+    progress: u64,
 
-    pub fn write(&self, filepath: &str) {
-        let mut guess_seq: Vec<Vec<Guess>> = (0..self.ans_until).map(|_| Vec::new()).collect();
-        self.dfs_build_guess_seq(&mut guess_seq, &self.pokemons.all_ans);
-
-        let mut f = fs::File::create(filepath).unwrap();
-        for guess in &guess_seq {
-            f.write_all(
-                format!(
-                    "{}\n",
-                    guess
-                        .iter()
-                        .map(|g| g.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                )
-                .as_bytes(),
-            )
-            .unwrap();
-        }
-    }
+    /// print progress as JSON lines
+    #[argh(switch)]
+    progress_json: bool,
 
-    fn dfs_build_guess_seq(&self, guess_seq: &mut Vec<Vec<Guess>>, rem_ans: &Vec<Answer>) {
-        if rem_ans.len() == 1 {
-            guess_seq[rem_ans[0]].push(rem_ans[0]);
-            return;
-        }
-        if rem_ans.len() == 2 {
-            guess_seq[rem_ans[0]].push(rem_ans[0]);
-            guess_seq[rem_ans[1]].push(rem_ans[0]);
-            guess_seq[rem_ans[1]].push(rem_ans[1]);
-            return;
-        }
+    /// the number of threads
+    #[argh(option, short = 't', default = "default_num_threads()")]
+    num_threads: usize,
 
-        let (guess, part) = {
-            let cache = self.cache.lock().unwrap();
-            let rem_id = *cache.set_id.get(rem_ans).unwrap();
-            let (_, guess, part) = cache.memo[&rem_id].clone();
-            (guess, part)
-        };
+    /// the filepath of decision tree output, written as JSON if it ends with .json
+    #[argh(option, short = 'o')]
+    output: String,
 
-        for ans in rem_ans {
-            guess_seq[*ans].push(guess.clone());
-        }
-        for s in part.values() {
-            self.dfs_build_guess_seq(guess_seq, s);
-        }
-    }
-}
+    /// the filepath of the web bundle (docs/js/const.js) to add the tree to
+    #[argh(option)]
+    const_js: Option<String>,
 
-#[derive(FromArgs)]
-/// Minimize expectation of the number of guess
-struct Args {
-    /// the number of answer pokemons
+    /// the mode name of the tree in the web bundle (defaults to --answers)
     #[argh(option)]
-    ans_until: usize,
+    label: Option<String>,
 
-    /// the number of guess pokemons
+    /// the filepath of checkpoint to write periodically (defaults to --resume)
     #[argh(option)]
-    guess_until: usize,
+    checkpoint: Option<String>,
 
-    /// the number of threads
-    #[argh(option, short = 't', default = "default_num_threads()")]
-    num_threads: usize,
+    /// the interval of writing checkpoint in seconds
+    #[argh(option, default = "600")]
+    checkpoint_interval: u64,
 
-    /// the filepath of decision tree output
-    #[argh(option, short = 'o')]
-    output: String,
+    /// the filepath of checkpoint to resume from
+    #[argh(option)]
+    resume: Option<String>,
 }
 fn default_num_threads() -> usize {
     1
@@ -359,8 +126,74 @@ fn default_num_threads() -> usize {
 
 fn main() {
     let args: Args = argh::from_env();
+    let checkpoint = args.checkpoint.clone().or_else(|| args.resume.clone());
+    let label = args.label.as_ref().or(args.answers.as_ref());
+    if args.const_js.is_some() && label.is_none() {
+        eprintln!("error: --const-js needs --label or --answers");
+        std::process::exit(1);
+    }
 
-    let solver = Solver::new(args.ans_until, args.guess_until);
+    let words = or_exit(WordList::from_arg(args.words.as_deref()));
+    let mut builder = SolverBuilder::new()
+        .word_len(args.word_len)
+        .objective(args.objective)
+        .hard(args.hard)
+        .lb_depth(args.lb_depth);
+    if let Some(ans_until) = args
+        .ans_until
+        .or(or_exit(words.until_arg(args.answers.as_deref())))
+    {
+        builder = builder.ans_until(ans_until);
+    }
+    if let Some(guess_until) = args
+        .guess_until
+        .or(or_exit(words.until_arg(args.guesses.as_deref())))
+    {
+        builder = builder.guess_until(guess_until);
+    }
+    if let Some(time_limit) = args.time_limit {
+        builder = builder.time_limit(Duration::from_secs_f64(time_limit));
+    }
+    if let Some(node_limit) = args.node_limit {
+        builder = builder.node_limit(node_limit);
+    }
+    if let Some(filepath) = &args.weights {
+        match words.load_weights(filepath) {
+            Ok(weights) => builder = builder.weights(weights),
+            Err(e) => {
+                eprintln!("error: {}: {}", filepath, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(heuristic) = &args.heuristic {
+        builder = builder
+            .greedy_heuristic(heuristic.clone())
+            .order_heuristic(heuristic.clone());
+    }
+    if let Some(heuristic) = &args.order_heuristic {
+        builder = builder.order_heuristic(heuristic.clone());
+    }
+    builder = builder
+        .answer_filter(or_exit(WordFilter::load(
+            &words,
+            args.allow_answers.as_deref(),
+            args.deny_answers.as_deref(),
+        )))
+        .guess_filter(or_exit(WordFilter::load(
+            &words,
+            args.allow_guesses.as_deref(),
+            args.deny_guesses.as_deref(),
+        )));
+    let mut solver = or_exit(builder.build(&words));
+
+    if let Some(resume) = &args.resume {
+        if let Err(e) = solver.resume(resume) {
+            eprintln!("error: {}: {}", resume, e);
+            std::process::exit(1);
+        }
+        println!("resumed from {}", resume);
+    }
 
     //let guard = pprof::ProfilerGuard::new(100).unwrap();
 
@@ -370,7 +203,72 @@ fn main() {
         .unwrap();
 
     let start = Instant::now();
-    pool.install(|| solver.build_best_solution());
+    let solution = thread::scope(|scope| {
+        // 探索が終わって tx が落ちるまで, 一定間隔で途中経過とチェックポイントを書き出す.
+        let solver = &solver;
+        let (progress_tx, progress_rx) = mpsc::channel::<()>();
+        if args.progress > 0 {
+            let interval = Duration::from_secs(args.progress);
+            let json = args.progress_json;
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = progress_rx.recv_timeout(interval) {
+                    let progress = solver.progress(start.elapsed().as_secs_f64());
+                    if json {
+                        eprintln!("{}", serde_json::to_string(&progress).unwrap());
+                    } else {
+                        eprintln!("{}", progress);
+                    }
+                }
+            });
+        }
+        let (checkpoint_tx, checkpoint_rx) = mpsc::channel::<()>();
+        if let Some(filepath) = &checkpoint {
+            let interval = Duration::from_secs(args.checkpoint_interval);
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = checkpoint_rx.recv_timeout(interval) {
+                    if let Err(e) = solver.save_checkpoint(filepath) {
+                        eprintln!("warning: {}: {}", filepath, e);
+                    }
+                }
+            });
+        }
+        let solution = pool.install(|| solver.solve());
+        drop(progress_tx);
+        drop(checkpoint_tx);
+        solution
+    });
+
+    let n = solver.total_weight();
+    let show = |name: &str, total: Score| {
+        println!("{}: {} = {}/{}", name, total as f32 / n as f32, total, n);
+    };
+    if solution.aborted {
+        println!("探索を打ち切りました.");
+    }
+    if let Some(worst_lower_bound) = solution.worst_lower_bound {
+        if solution.aborted {
+            println!("最悪回数(下界): {}", worst_lower_bound);
+        }
+        match solution.worst {
+            Some(worst) => println!("最悪回数(最適): {}", worst),
+            None => show("期待回数(貪欲)", solution.total),
+        }
+    }
+    if solution.worst.is_some() || solution.worst_lower_bound.is_none() {
+        if solution.aborted {
+            show("期待回数(暫定)", solution.total);
+        } else {
+            show("期待回数(最適)", solution.total);
+        }
+    }
+    if solution.aborted && solution.worst_lower_bound.is_none() {
+        let lb = solution.lower_bound;
+        show("期待回数(下界)", lb);
+        println!(
+            "gap: {:.2}%",
+            100.0 * (solution.total - lb) as f32 / lb as f32
+        );
+    }
     println!(
         "elapsed time: {:?} [sec]",
         start.elapsed().as_nanos() as f32 / 1_000_000_000 as f32
@@ -388,12 +286,27 @@ fn main() {
     //    Err(_) => {}
     //};
 
-    println!("best.len(): {:?}", solver.cache.lock().unwrap().best.len());
-    println!("memo.len(): {:?}", solver.cache.lock().unwrap().memo.len());
+    for (name, len) in solver.cache_sizes() {
+        println!("{}.len(): {:?}", name, len);
+    }
+    let progress = solver.progress(start.elapsed().as_secs_f64());
     println!(
-        "lb_memo.len(): {:?}",
-        solver.cache.lock().unwrap().lb_memo.len()
+        "nodes: {}, pruned nodes: {}, pruned guesses: {}",
+        progress.nodes, progress.pruned_nodes, progress.pruned_guesses
     );
 
+    if let Some(filepath) = &checkpoint {
+        if let Err(e) = solver.save_checkpoint(filepath) {
+            eprintln!("warning: {}: {}", filepath, e);
+        }
+    }
+
     solver.write(&args.output);
+
+    if let (Some(filepath), Some(label)) = (&args.const_js, label) {
+        if let Err(e) = solver.write_const_js(filepath, label) {
+            eprintln!("error: {}: {}", filepath, e);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,126 @@
+use argh::FromArgs;
+use std::collections::BTreeMap;
+
+use wordle_pokemon::{cli::*, judge::*, pokemon::*, tree::*};
+
+#[derive(FromArgs)]
+/// Verify decision tree
+struct Args {
+    /// the filepath of decision tree input, .txt or .json
+    #[argh(option, short = 'i')]
+    input: String,
+
+    /// the filepath of word list (defaults to the built-in pokemons)
+    #[argh(option)]
+    words: Option<String>,
+
+    /// the last generation of answer pokemons, e.g. BW (defaults to the tree)
+    #[argh(option)]
+    answers: Option<String>,
+
+    /// the last generation of guess pokemons, e.g. SWSH (defaults to the tree)
+    #[argh(option)]
+    guesses: Option<String>,
+
+    /// the filepath of answers the tree was solved with, one name per line (only these answers within --answers)
+    #[argh(option)]
+    allow_answers: Option<String>,
+
+    /// the filepath of answers the tree was solved without, one name per line
+    #[argh(option)]
+    deny_answers: Option<String>,
+
+    /// also check that every guess is allowed in hard mode
+    #[argh(switch)]
+    hard: bool,
+}
+
+// 答え ans について木を辿り, 当てるまでの宣言回数を返す.
+fn replay(tree: &DecisionTree, root: &Node, ans: &Answer, hard: bool) -> Result<usize, String> {
+    let pokemons = &tree.pokemons;
+    let mut node = root;
+    let mut next;
+    let mut history: Vec<(Guess, Judge)> = vec![];
+    loop {
+        let guess = match node {
+            Node::NonTerminal { guess, .. } => *guess,
+            Node::Terminal => return Err("the tree ends without a guess".to_string()),
+        };
+        if hard && !tree.judge_table.is_consistent(&guess, &history) {
+            return Err(format!(
+                "{} is not allowed in hard mode after {} guesses",
+                pokemons.names[guess],
+                history.len()
+            ));
+        }
+        let judge = tree.judge_table.judge(&guess, ans);
+        history.push((guess, judge));
+        next = node.next(&judge).ok_or_else(|| {
+            format!(
+                "no edge for {} after {}",
+                Feedback::from_judge(judge, pokemons.word_len).unwrap(),
+                pokemons.names[guess]
+            )
+        })?;
+        node = &next;
+        if let Node::Terminal = node {
+            if judge != tree.judge_table.all_correct {
+                return Err(format!(
+                    "the tree ends with {} after {}",
+                    Feedback::from_judge(judge, pokemons.word_len).unwrap(),
+                    pokemons.names[guess]
+                ));
+            }
+            return Ok(history.len());
+        }
+    }
+}
+
+fn main() {
+    let args: Args = argh::from_env();
+
+    let words = or_exit(WordList::from_arg(args.words.as_deref()));
+    let (tree, root) = or_exit(DecisionTree::load_root(
+        &args.input,
+        &words,
+        or_exit(words.until_arg(args.answers.as_deref())),
+        or_exit(words.until_arg(args.guesses.as_deref())),
+        &or_exit(WordFilter::load(
+            &words,
+            args.allow_answers.as_deref(),
+            args.deny_answers.as_deref(),
+        )),
+    ));
+
+    let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut failed = 0;
+    for ans in tree.pokemons.all_ans.iter() {
+        match replay(&tree, &root, ans, args.hard) {
+            Ok(depth) => *histogram.entry(depth).or_default() += 1,
+            Err(e) => {
+                println!("NG {}: {}", tree.pokemons.names[*ans], e);
+                failed += 1;
+            }
+        }
+    }
+
+    let n = tree.pokemons.all_ans.len();
+    let total: usize = histogram.iter().map(|(depth, cnt)| depth * cnt).sum();
+    println!("答え: {}匹", n);
+    println!("合計: {}/{}", total, n - failed);
+    println!("平均: {:.4}", total as f64 / (n - failed).max(1) as f64);
+    println!("最悪: {}", histogram.keys().last().unwrap_or(&0));
+    for (depth, cnt) in histogram.iter() {
+        println!("{}回: {}", depth, cnt);
+    }
+
+    if failed > 0 {
+        println!("{}匹の答えを当てられません", failed);
+        std::process::exit(1);
+    }
+    if total != root.total() {
+        println!("合計が木の合計 {} と一致しません", root.total());
+        std::process::exit(1);
+    }
+    println!("OK");
+}
@@ -0,0 +1,50 @@
+use std::fs;
+use std::io;
+
+// docs/js/const.js の tree_json を読み書きする. 各要素はモード名と決定木の JSON の組.
+// const tree_json = {
+// 	"DP": `{"guess":...}`
+// 	,
+// }
+const HEADER: &str = "const tree_json = {\n";
+const FOOTER: &str = "}";
+
+pub fn read_const_js(filepath: &str) -> io::Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(filepath)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a tree_json bundle");
+
+    let mut rest = content.strip_prefix(HEADER).ok_or_else(invalid)?;
+    let mut entries = vec![];
+    while let Some(start) = rest.find('"') {
+        let (label, after) = rest[start + 1..].split_once('"').ok_or_else(invalid)?;
+        let (_, after) = after.split_once('`').ok_or_else(invalid)?;
+        let (json, after) = after.split_once('`').ok_or_else(invalid)?;
+        entries.push((label.to_string(), json.to_string()));
+        rest = after;
+    }
+
+    Ok(entries)
+}
+
+pub fn write_const_js(filepath: &str, entries: &[(String, String)]) -> io::Result<()> {
+    let mut content = HEADER.to_string();
+    for (label, json) in entries {
+        content += &format!("\t\"{}\": `{}`\n\t,\n", label, json);
+    }
+    content += FOOTER;
+    fs::write(filepath, content)
+}
+
+// label の決定木を json に置き換える. なければ末尾に追加する.
+pub fn update_const_js(filepath: &str, label: &str, json: String) -> io::Result<()> {
+    let mut entries = match read_const_js(filepath) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e),
+    };
+    match entries.iter_mut().find(|(l, _)| l == label) {
+        Some(entry) => entry.1 = json,
+        None => entries.push((label.to_string(), json)),
+    }
+    write_const_js(filepath, &entries)
+}
@@ -0,0 +1,9 @@
+use std::fmt;
+
+// 各コマンドで共通の, エラーを "error: ..." と表示して終了する処理.
+pub fn or_exit<T, E: fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    })
+}
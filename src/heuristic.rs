@@ -0,0 +1,95 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+// 宣言で分かれた答えの集合の大きさ (当たりを除く. 答えに重みがあれば重みの和) から,
+// 宣言の悪さを見積もる. 小さいほど良い.
+pub trait GuessHeuristic: Send + Sync {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32;
+}
+
+// エントロピーを最大化する. sum x log2 x.
+pub struct Entropy;
+impl GuessHeuristic for Entropy {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32 {
+        sizes.map(|x| x as f32 * (x as f32).log2()).sum()
+    }
+}
+
+// 答えを含む集合の大きさの期待値を最小化する. sum x^2.
+pub struct ExpectedSize;
+impl GuessHeuristic for ExpectedSize {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32 {
+        sizes.map(|x| (x * x) as f32).sum()
+    }
+}
+
+// 最も大きい集合を最小化する.
+pub struct MaxBucket;
+impl GuessHeuristic for MaxBucket {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32 {
+        sizes.max().unwrap_or(0) as f32
+    }
+}
+
+// 集合の数を最大化する.
+pub struct NumBuckets;
+impl GuessHeuristic for NumBuckets {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32 {
+        -(sizes.count() as f32)
+    }
+}
+
+// 大きさの期待値とエントロピーの重み付き和. sum (weight x + log2 x) x.
+pub struct Weighted(pub f32);
+impl GuessHeuristic for Weighted {
+    fn penalty(&self, sizes: &mut dyn Iterator<Item = usize>) -> f32 {
+        sizes
+            .map(|x| {
+                let x = x as f32;
+                (self.0 * x + x.log2()) * x
+            })
+            .sum()
+    }
+}
+
+// 名前で選べるようにしたもの. weight は weight:0.1 のように重みを付ける.
+#[derive(Clone)]
+pub struct Heuristic {
+    pub name: String,
+    pub heuristic: Arc<dyn GuessHeuristic>,
+}
+impl Heuristic {
+    pub fn new(name: &str, heuristic: impl GuessHeuristic + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            heuristic: Arc::new(heuristic),
+        }
+    }
+
+    pub fn penalty(&self, mut sizes: impl Iterator<Item = usize>) -> f32 {
+        self.heuristic.penalty(&mut sizes)
+    }
+}
+impl FromStr for Heuristic {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None => match s {
+                "entropy" => Ok(Self::new(s, Entropy)),
+                "expected-size" => Ok(Self::new(s, ExpectedSize)),
+                "max-bucket" => Ok(Self::new(s, MaxBucket)),
+                "num-buckets" => Ok(Self::new(s, NumBuckets)),
+                "weight" => Ok(Self::new("weight:0.1", Weighted(0.1))),
+                _ => Err(format!(
+                    "unknown heuristic: {} (expected entropy, expected-size, max-bucket, num-buckets or weight:<w>)",
+                    s
+                )),
+            },
+            Some(("weight", w)) => match w.parse() {
+                Ok(w) => Ok(Self::new(s, Weighted(w))),
+                Err(_) => Err(format!("invalid weight: {}", w)),
+            },
+            _ => Err(format!("unknown heuristic: {}", s)),
+        }
+    }
+}
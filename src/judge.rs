@@ -1,59 +1,213 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
-use super::pokemon::*;
+use super::{pokemon::*, word_set::*};
 
 pub type Judge = usize;
 pub type Partition = HashMap<Judge, Vec<Answer>>;
+pub type SetPartition = Vec<(Judge, WordSet)>;
 
-pub const ALL_CORRECT: Judge = ((Status::Correct as usize) << 2 * 0)
-    + ((Status::Correct as usize) << 2 * 1)
-    + ((Status::Correct as usize) << 2 * 2)
-    + ((Status::Correct as usize) << 2 * 3)
-    + ((Status::Correct as usize) << 2 * 4);
+// Judge は 1 文字 2 ビットなので, usize に収まる文字数まで.
+pub const MAX_WORD_LEN: usize = usize::BITS as usize / 2;
 
-#[derive(Clone, Copy)]
+pub fn all_correct(word_len: usize) -> Judge {
+    (0..word_len)
+        .map(|i| (Status::Correct as usize) << (2 * i))
+        .sum()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Status {
     Nowhere = 0,
     Wrong = 1,
     Correct = 2,
 }
 
+impl Status {
+    pub fn from_digit(c: char) -> Option<Self> {
+        match c {
+            '0' => Some(Status::Nowhere),
+            '1' => Some(Status::Wrong),
+            '2' => Some(Status::Correct),
+            _ => None,
+        }
+    }
+
+    pub fn from_emoji(c: char) -> Option<Self> {
+        match c {
+            '⬛' | '⬜' => Some(Status::Nowhere),
+            '🟨' => Some(Status::Wrong),
+            '🟩' => Some(Status::Correct),
+            _ => None,
+        }
+    }
+
+    pub fn to_digit(self) -> char {
+        match self {
+            Status::Nowhere => '0',
+            Status::Wrong => '1',
+            Status::Correct => '2',
+        }
+    }
+
+    pub fn to_emoji(self) -> char {
+        match self {
+            Status::Nowhere => '⬛',
+            Status::Wrong => '🟨',
+            Status::Correct => '🟩',
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FeedbackError {
+    InvalidChar(char),
+    MixedChars,
+    InvalidLength(usize),
+    WrongLength { expected: usize, found: usize },
+    InvalidJudge(Judge),
+}
+
+impl fmt::Display for FeedbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedbackError::InvalidChar(c) => {
+                write!(f, "invalid character '{}' (expected 0, 1, 2 or 🟩🟨⬛)", c)
+            }
+            FeedbackError::MixedChars => write!(f, "digits and emoji are mixed"),
+            FeedbackError::InvalidLength(len) => {
+                write!(
+                    f,
+                    "expected 1 to {} characters, found {}",
+                    MAX_WORD_LEN, len
+                )
+            }
+            FeedbackError::WrongLength { expected, found } => {
+                write!(f, "expected {} characters, found {}", expected, found)
+            }
+            FeedbackError::InvalidJudge(judge) => write!(f, "invalid judge value {}", judge),
+        }
+    }
+}
+
+impl std::error::Error for FeedbackError {}
+
+// 1文字ごとの判定. 数字 ("02110") か絵文字 ("⬛🟩🟨🟨⬛") で読み書きする. 両者を混ぜてはいけない.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Feedback(pub Vec<Status>);
+
+impl Feedback {
+    pub fn parse(s: &str, word_len: usize) -> Result<Self, FeedbackError> {
+        let feedback: Self = s.parse()?;
+        if feedback.0.len() != word_len {
+            return Err(FeedbackError::WrongLength {
+                expected: word_len,
+                found: feedback.0.len(),
+            });
+        }
+        Ok(feedback)
+    }
+
+    // 各文字が 0, 1, 2 のいずれかで, word_len 文字に収まる judge だけを受け付ける.
+    pub fn from_judge(judge: Judge, word_len: usize) -> Result<Self, FeedbackError> {
+        if word_len == 0 || word_len > MAX_WORD_LEN {
+            return Err(FeedbackError::InvalidLength(word_len));
+        }
+        if word_len < MAX_WORD_LEN && judge >> (2 * word_len) != 0 {
+            return Err(FeedbackError::InvalidJudge(judge));
+        }
+        (0..word_len)
+            .map(|i| match judge >> (2 * i) & 0b11 {
+                0 => Ok(Status::Nowhere),
+                1 => Ok(Status::Wrong),
+                2 => Ok(Status::Correct),
+                _ => Err(FeedbackError::InvalidJudge(judge)),
+            })
+            .collect::<Result<Vec<Status>, _>>()
+            .map(Self)
+    }
+
+    pub fn to_judge(&self) -> Judge {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, status)| (*status as usize) << (2 * i))
+            .sum()
+    }
+
+    pub fn to_emoji(&self) -> String {
+        self.0.iter().map(|status| status.to_emoji()).collect()
+    }
+}
+
+impl FromStr for Feedback {
+    type Err = FeedbackError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.trim().chars().filter(|c| *c != '\u{fe0f}').collect();
+        if chars.is_empty() || chars.len() > MAX_WORD_LEN {
+            return Err(FeedbackError::InvalidLength(chars.len()));
+        }
+        // 1 文字目が数字か絵文字かで, 全体の書き方を決める.
+        let digits = Status::from_digit(chars[0]).is_some();
+        chars
+            .iter()
+            .map(|c| match (Status::from_digit(*c), Status::from_emoji(*c)) {
+                (Some(status), _) if digits => Ok(status),
+                (_, Some(status)) if !digits => Ok(status),
+                (Some(_), _) | (_, Some(_)) => Err(FeedbackError::MixedChars),
+                (None, None) => Err(FeedbackError::InvalidChar(*c)),
+            })
+            .collect::<Result<Vec<Status>, _>>()
+            .map(Self)
+    }
+}
+
+impl fmt::Display for Feedback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: String = self.0.iter().map(|status| status.to_digit()).collect();
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Default)]
 pub struct JudgeTable {
     pub ans_until: usize,
     pub guess_until: usize,
+    pub all_correct: Judge,
     data: Vec<Vec<Judge>>,
 }
 impl JudgeTable {
-    pub fn new(ans_until: usize, guess_until: usize) -> Self {
-        let pokemons = PokemonList::new(ans_until, guess_until);
+    pub fn new(pokemons: &PokemonList) -> Self {
+        let ans_until = pokemons.is_valid_ans.len();
+        let guess_until = pokemons.is_valid_guess.len();
 
         let judge = |guess: &Guess, ans: &Answer| -> Judge {
             assert!(pokemons.is_valid_guess[*guess]);
-            assert!(pokemons.is_valid_ans[*ans]);
 
-            let guess: Vec<char> = POKEMONS[*guess].chars().collect();
-            let ans: Vec<char> = POKEMONS[*ans].chars().collect();
+            let guess: Vec<char> = pokemons.names[*guess].chars().collect();
+            let ans: Vec<char> = pokemons.names[*ans].chars().collect();
 
             let (mut ret, mut guess_used, mut ans_used) = (0, 0, 0);
-            for i in 0..guess.len() {
+            for i in 0..guess.len().min(ans.len()) {
                 if guess[i] == ans[i] {
-                    ret |= (Status::Correct as usize) << 2 * i;
+                    ret |= (Status::Correct as usize) << (2 * i);
                     guess_used |= 1 << i;
                     ans_used |= 1 << i;
                 }
             }
 
-            for i in 0..guess.len() {
+            for (i, g) in guess.iter().enumerate() {
                 if (guess_used >> i & 1) > 0 {
                     continue;
                 }
-                for j in 0..5 {
+                for (j, a) in ans.iter().enumerate() {
                     if (ans_used >> j & 1) > 0 {
                         continue;
                     }
-                    if guess[i] == ans[j] {
-                        ret |= (Status::Wrong as usize) << 2 * i;
+                    if g == a {
+                        ret |= (Status::Wrong as usize) << (2 * i);
                         guess_used |= 1 << i;
                         ans_used |= 1 << j;
                         break;
@@ -63,10 +217,19 @@ impl JudgeTable {
             ret
         };
 
-        let data = (0..ans_until)
+        // ハードモードの判定のため, 答えになり得る長さの宣言も答えとして judge しておく.
+        let data = (0..ans_until.max(guess_until))
             .map(|ans| {
-                if pokemons.is_valid_ans[ans] {
-                    (0..guess_until).map(|guess| judge(&guess, &ans)).collect()
+                if pokemons.names[ans].chars().count() == pokemons.word_len {
+                    (0..guess_until)
+                        .map(|guess| {
+                            if pokemons.is_valid_guess[guess] {
+                                judge(&guess, &ans)
+                            } else {
+                                0
+                            }
+                        })
+                        .collect()
                 } else {
                     vec![]
                 }
@@ -76,23 +239,173 @@ impl JudgeTable {
         Self {
             ans_until,
             guess_until,
+            all_correct: all_correct(pokemons.word_len),
             data,
         }
     }
 
     pub fn judge(&self, guess: &Guess, ans: &Answer) -> Judge {
-        return self.data[*ans][*guess];
+        self.data[*ans][*guess]
     }
 
-    pub fn partition(&self, ans_rem: &Vec<Answer>, guess: &Guess) -> Partition {
+    // word が答えだとしても, これまでの (宣言, judge) と矛盾しないか.
+    pub fn is_consistent(&self, word: &Pokemon, history: &[(Guess, Judge)]) -> bool {
+        match self.data.get(*word) {
+            Some(row) if !row.is_empty() => history
+                .iter()
+                .all(|(guess, judge)| row.get(*guess) == Some(judge)),
+            _ => false,
+        }
+    }
+
+    pub fn partition(&self, ans_rem: &[Answer], guess: &Guess) -> Partition {
         let mut ret: Partition = HashMap::new();
         for ans in ans_rem.iter() {
             let judge = self.judge(guess, ans);
-            if judge == ALL_CORRECT {
+            if judge == self.all_correct {
                 continue;
             }
-            ret.entry(judge).or_insert(Vec::new()).push(*ans);
+            ret.entry(judge).or_default().push(*ans);
         }
         ret
     }
+
+    // partition の WordSet 版. ans_rem と返す集合には, 答えの一覧 answers での位置を入れる.
+    // judge は all_correct 以下なので, 配列で振り分け先を引く.
+    // 配列はスレッドごとに使い回し, 使った所だけ u16::MAX に戻す.
+    pub fn partition_set(
+        &self,
+        ans_rem: &WordSet,
+        guess: &Guess,
+        answers: &[Answer],
+    ) -> SetPartition {
+        PARTITION_INDEX.with(|index| {
+            let mut index = index.borrow_mut();
+            if index.len() <= self.all_correct {
+                index.resize(self.all_correct + 1, u16::MAX);
+            }
+            let mut ret: SetPartition = Vec::new();
+            for i in ans_rem.iter() {
+                let judge = self.judge(guess, &answers[i]);
+                if judge == self.all_correct {
+                    continue;
+                }
+                if index[judge] == u16::MAX {
+                    index[judge] = ret.len() as u16;
+                    ret.push((judge, WordSet::default()));
+                }
+                ret[index[judge] as usize].1.insert(i);
+            }
+            for (judge, _) in ret.iter() {
+                index[*judge] = u16::MAX;
+            }
+            ret
+        })
+    }
+}
+
+thread_local! {
+    static PARTITION_INDEX: RefCell<Vec<u16>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_digits_and_emoji() {
+        let expected = Feedback(vec![
+            Status::Nowhere,
+            Status::Correct,
+            Status::Wrong,
+            Status::Wrong,
+            Status::Nowhere,
+        ]);
+        assert_eq!(Feedback::parse("02110", 5), Ok(expected.clone()));
+        assert_eq!(Feedback::parse(" ⬛🟩🟨🟨⬜\n", 5), Ok(expected.clone()));
+        // 絵文字の後ろの異体字セレクタは読み飛ばす.
+        assert_eq!(
+            Feedback::parse("⬛\u{fe0f}🟩🟨🟨⬛\u{fe0f}", 5),
+            Ok(expected.clone())
+        );
+        assert_eq!(expected.to_string(), "02110");
+        assert_eq!(expected.to_emoji(), "⬛🟩🟨🟨⬛");
+    }
+
+    #[test]
+    fn reject_invalid_feedback() {
+        assert_eq!(
+            "02x10".parse::<Feedback>(),
+            Err(FeedbackError::InvalidChar('x'))
+        );
+        assert_eq!(
+            "🟩a".parse::<Feedback>(),
+            Err(FeedbackError::InvalidChar('a'))
+        );
+        assert_eq!("02🟨10".parse::<Feedback>(), Err(FeedbackError::MixedChars));
+        assert_eq!("🟩2".parse::<Feedback>(), Err(FeedbackError::MixedChars));
+        assert_eq!("".parse::<Feedback>(), Err(FeedbackError::InvalidLength(0)));
+        assert_eq!(
+            "0".repeat(MAX_WORD_LEN + 1).parse::<Feedback>(),
+            Err(FeedbackError::InvalidLength(MAX_WORD_LEN + 1))
+        );
+        assert_eq!(
+            Feedback::parse("0211", 5),
+            Err(FeedbackError::WrongLength {
+                expected: 5,
+                found: 4
+            })
+        );
+    }
+
+    #[test]
+    fn judge_round_trip() {
+        let feedback = Feedback::parse("02110", 5).unwrap();
+        assert_eq!(Feedback::from_judge(feedback.to_judge(), 5), Ok(feedback));
+        assert_eq!(
+            Feedback::from_judge(all_correct(5), 5).unwrap().to_string(),
+            "22222"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_judge() {
+        // 1 文字目が 3 (0b11).
+        assert_eq!(
+            Feedback::from_judge(0b11, 5),
+            Err(FeedbackError::InvalidJudge(0b11))
+        );
+        // word_len 文字より上のビットが立っている.
+        assert_eq!(
+            Feedback::from_judge(1 << 10, 5),
+            Err(FeedbackError::InvalidJudge(1 << 10))
+        );
+        assert_eq!(
+            Feedback::from_judge(0, MAX_WORD_LEN + 1),
+            Err(FeedbackError::InvalidLength(MAX_WORD_LEN + 1))
+        );
+    }
+
+    #[test]
+    fn partition_set_agrees_with_partition() {
+        let words = WordList::default();
+        let pokemons =
+            PokemonList::from_words(&words, WORD_LEN, words.until("DP").unwrap(), 200).unwrap();
+        let judge_table = JudgeTable::new(&pokemons);
+        let answers = &pokemons.all_ans;
+        // 位置の集合として, 1 つおきの答えを残す.
+        let positions: Vec<usize> = (0..answers.len()).step_by(2).collect();
+        let rem_ans: Vec<Answer> = positions.iter().map(|i| answers[*i]).collect();
+        let rem_set = WordSet::from_slice(&positions);
+
+        for guess in pokemons.all_guess.iter() {
+            let part = judge_table.partition(&rem_ans, guess);
+            let part_set = judge_table.partition_set(&rem_set, guess, answers);
+            assert_eq!(part_set.len(), part.len());
+            for (judge, s) in part_set {
+                let s: Vec<Answer> = s.iter().map(|i| answers[i]).collect();
+                assert_eq!(Some(&s), part.get(&judge));
+            }
+        }
+    }
 }
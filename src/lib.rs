@@ -1,3 +1,9 @@
+pub mod bundle;
+pub mod cli;
+pub mod heuristic;
 pub mod judge;
+pub mod live;
 pub mod pokemon;
+pub mod solver;
 pub mod tree;
+pub mod word_set;
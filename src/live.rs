@@ -0,0 +1,107 @@
+use super::{judge::*, pokemon::*, solver::*};
+
+// 残りの答えがこの数以下なら, 期待回数が最小になる宣言を全探索で求める.
+pub const EXACT_LIMIT: usize = 8;
+
+// 決定木に沿わない宣言にも対応するため, 実際の (宣言, judge) から残りの答えを絞り込む.
+pub struct LiveSolver<'a> {
+    words: &'a WordList,
+    pokemons: &'a PokemonList,
+    judge_table: &'a JudgeTable,
+    hard: bool,
+    pub rem_ans: Vec<Answer>,
+    pub history: Vec<(Guess, Judge)>,
+}
+
+impl<'a> LiveSolver<'a> {
+    pub fn new(
+        words: &'a WordList,
+        pokemons: &'a PokemonList,
+        judge_table: &'a JudgeTable,
+        hard: bool,
+    ) -> Self {
+        Self {
+            words,
+            pokemons,
+            judge_table,
+            hard,
+            rem_ans: pokemons.all_ans.clone(),
+            history: vec![],
+        }
+    }
+
+    pub fn play(&mut self, guess: Guess, judge: Judge) {
+        let judge_table = self.judge_table;
+        self.rem_ans
+            .retain(|ans| judge_table.judge(&guess, ans) == judge);
+        self.history.push((guess, judge));
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if self.history.pop().is_none() {
+            return false;
+        }
+        let judge_table = self.judge_table;
+        let history = &self.history;
+        self.rem_ans = self
+            .pokemons
+            .all_ans
+            .iter()
+            .filter(|ans| {
+                history
+                    .iter()
+                    .all(|(guess, judge)| judge_table.judge(guess, ans) == *judge)
+            })
+            .copied()
+            .collect();
+        true
+    }
+
+    pub fn restart(&mut self) {
+        self.rem_ans = self.pokemons.all_ans.clone();
+        self.history.clear();
+    }
+
+    // 次に宣言できるポケモン.
+    pub fn guesses(&self) -> Vec<Guess> {
+        if self.hard {
+            self.pokemons
+                .hard_mode_guesses(self.judge_table, &self.history)
+        } else {
+            self.pokemons.all_guess.clone()
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.rem_ans.len() <= EXACT_LIMIT
+    }
+
+    // 残りの答えと次に宣言できるポケモンに絞った Solver で, 少なければ最適な宣言を, 多ければ貪欲な宣言を選ぶ.
+    pub fn suggest(&self) -> Result<Option<Guess>, String> {
+        if self.rem_ans.is_empty() {
+            return Ok(None);
+        }
+        if self.rem_ans.len() <= 2 {
+            return Ok(Some(self.rem_ans[0]));
+        }
+
+        let only = |names: Vec<Pokemon>| WordFilter {
+            allow: Some(names),
+            deny: vec![],
+        };
+        let solver = SolverBuilder::new()
+            .word_len(self.pokemons.word_len)
+            .ans_until(self.pokemons.is_valid_ans.len())
+            .guess_until(self.pokemons.is_valid_guess.len())
+            .hard(self.hard)
+            .answer_filter(only(self.rem_ans.clone()))
+            .guess_filter(only(self.guesses()))
+            .build(self.words)?;
+        if self.is_exact() {
+            solver.solve();
+        } else {
+            solver.solve_greedy();
+        }
+        Ok(Some(solver.first_guess()))
+    }
+}
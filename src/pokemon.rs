@@ -1,34 +1,326 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use super::judge::*;
+
 pub type Pokemon = usize;
 pub type Guess = Pokemon;
 pub type Answer = Pokemon;
 
+pub const WORD_LEN: usize = 5;
+
+// 宣言・答えに使うポケモン名の一覧. 各行は名前と, 任意の世代やタグの列からなる.
+pub struct WordList {
+    pub names: Vec<String>,
+    pub tags: Vec<Vec<String>>,
+}
+
+impl Default for WordList {
+    fn default() -> Self {
+        let tags = (0..POKEMONS.len())
+            .map(|i| {
+                let (generation, _) = GENERATIONS.iter().find(|(_, until)| i < *until).unwrap();
+                vec![generation.to_string()]
+            })
+            .collect();
+
+        Self {
+            names: POKEMONS.iter().map(|s| s.to_string()).collect(),
+            tags,
+        }
+    }
+}
+
+impl WordList {
+    pub fn load(filepath: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(filepath).map_err(|e| e.to_string())?;
+        let mut ret = Self {
+            names: vec![],
+            tags: vec![],
+        };
+        let mut seen = HashSet::new();
+        for (lineno, line) in content.lines().enumerate() {
+            let mut cols = line.split_whitespace().map(|s| s.to_string());
+            let name = match cols.next() {
+                Some(name) => name,
+                None => continue,
+            };
+            if !seen.insert(name.clone()) {
+                return Err(format!("line {}: duplicate pokemon {}", lineno + 1, name));
+            }
+            ret.names.push(name);
+            ret.tags.push(cols.collect());
+        }
+        if ret.is_empty() {
+            return Err("the word list is empty".to_string());
+        }
+        Ok(ret)
+    }
+
+    // コマンドの --words の値から読む. None なら組み込みのポケモン.
+    pub fn from_arg(filepath: Option<&str>) -> Result<Self, String> {
+        match filepath {
+            Some(p) => Self::load(p).map_err(|e| format!("{}: {}", p, e)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    // 世代 generation までのポケモンの数. 最後にその世代のタグが付いたポケモンまでを数える.
+    pub fn until(&self, generation: &str) -> Result<usize, String> {
+        self.tags
+            .iter()
+            .rposition(|tags| tags.iter().any(|t| t.eq_ignore_ascii_case(generation)))
+            .map(|i| i + 1)
+            .ok_or_else(|| format!("unknown generation: {}", generation))
+    }
+
+    // コマンドの --answers, --guesses の値の世代までのポケモンの数. None ならそのまま None.
+    pub fn until_arg(&self, generation: Option<&str>) -> Result<Option<usize>, String> {
+        generation.map(|g| self.until(g)).transpose()
+    }
+
+    // 答えの重みを読む. 各行は名前と正の整数の重みで, 書かれていないポケモンの重みは 1.
+    // 返り値は単語の番号ごとの重み.
+    pub fn load_weights(&self, filepath: &str) -> Result<Vec<usize>, String> {
+        let index = self.index();
+        let mut weights = vec![1; self.len()];
+        let mut seen = vec![false; self.len()];
+        let content = fs::read_to_string(filepath).map_err(|e| e.to_string())?;
+        for (lineno, line) in content.lines().enumerate() {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let (name, weight) = match cols[..] {
+                [] => continue,
+                [name, weight] => (name, weight),
+                _ => return Err(format!("line {}: expected NAME WEIGHT", lineno + 1)),
+            };
+            let i = *index
+                .get(name)
+                .ok_or_else(|| format!("line {}: unknown pokemon {}", lineno + 1, name))?;
+            if seen[i] {
+                return Err(format!("line {}: duplicate pokemon {}", lineno + 1, name));
+            }
+            seen[i] = true;
+            weights[i] = match weight.parse() {
+                Ok(w) if w > 0 => w,
+                _ => {
+                    return Err(format!(
+                        "line {}: the weight must be a positive integer: {}",
+                        lineno + 1,
+                        weight
+                    ))
+                }
+            };
+        }
+        Ok(weights)
+    }
+
+    // 名前の一覧を読む. 各行は 1 つの名前で, 単語の番号の一覧を返す.
+    pub fn load_names(&self, filepath: &str) -> Result<Vec<Pokemon>, String> {
+        let index = self.index();
+        let content = fs::read_to_string(filepath).map_err(|e| e.to_string())?;
+        let mut ret = vec![];
+        for (lineno, line) in content.lines().enumerate() {
+            let name = line.trim();
+            if name.is_empty() {
+                continue;
+            }
+            let i = *index
+                .get(name)
+                .ok_or_else(|| format!("line {}: unknown pokemon {}", lineno + 1, name))?;
+            ret.push(i);
+        }
+        Ok(ret)
+    }
+
+    fn index(&self) -> HashMap<&str, Pokemon> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+// 答え・宣言に使うポケモンを名前の一覧で絞り込む.
+// allow があればそこに含まれるものだけを使い, deny に含まれるものは使わない.
+#[derive(Clone, Default)]
+pub struct WordFilter {
+    pub allow: Option<Vec<Pokemon>>,
+    pub deny: Vec<Pokemon>,
+}
+
+impl WordFilter {
+    // コマンドの --allow-*, --deny-* の値のファイルから読む.
+    pub fn load(words: &WordList, allow: Option<&str>, deny: Option<&str>) -> Result<Self, String> {
+        let load_names = |p: &str| words.load_names(p).map_err(|e| format!("{}: {}", p, e));
+        Ok(Self {
+            allow: allow.map(load_names).transpose()?,
+            deny: deny.map(load_names).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    fn apply(&self, is_valid: &mut [bool]) {
+        if let Some(allow) = &self.allow {
+            let mut allowed = vec![false; is_valid.len()];
+            for i in allow {
+                allowed[*i] = true;
+            }
+            for (valid, allowed) in is_valid.iter_mut().zip(allowed) {
+                *valid &= allowed;
+            }
+        }
+        for i in &self.deny {
+            if let Some(valid) = is_valid.get_mut(*i) {
+                *valid = false;
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PokemonList {
+    pub word_len: usize,
+    pub names: Vec<String>,
     pub all_ans: Vec<Answer>,
     pub all_guess: Vec<Guess>,
     pub is_valid_ans: Vec<bool>,
     pub is_valid_guess: Vec<bool>,
+    // 答えの重み. None なら全て 1 とみなす.
+    pub weights: Option<Vec<usize>>,
 }
 
 impl PokemonList {
-    pub fn new(ans_until: usize, guess_until: usize) -> Self {
+    pub fn new(ans_until: usize, guess_until: usize) -> Result<Self, String> {
+        Self::from_words(&WordList::default(), WORD_LEN, ans_until, guess_until)
+    }
+
+    // 答えは word_len 文字のポケモン, 宣言は word_len 文字以下のポケモン.
+    pub fn from_words(
+        words: &WordList,
+        word_len: usize,
+        ans_until: usize,
+        guess_until: usize,
+    ) -> Result<Self, String> {
+        if ans_until > words.len() || guess_until > words.len() {
+            return Err(format!(
+                "the word list has only {} pokemons (answers up to {}, guesses up to {} requested)",
+                words.len(),
+                ans_until,
+                guess_until
+            ));
+        }
+
+        let names: Vec<String> = words.names[..ans_until.max(guess_until)].to_vec();
+
         let is_valid_ans: Vec<bool> = (0..ans_until)
-            .map(|i| POKEMONS[i].chars().collect::<Vec<char>>().len() == 5)
+            .map(|i| names[i].chars().count() == word_len)
+            .collect();
+        let is_valid_guess: Vec<bool> = (0..guess_until)
+            .map(|i| names[i].chars().count() <= word_len)
             .collect();
-        let is_valid_guess: Vec<bool> = vec![true; guess_until];
 
         let all_ans: Vec<Answer> = (0..ans_until).filter(|&i| is_valid_ans[i]).collect();
         let all_guess: Vec<Guess> = (0..guess_until).filter(|&i| is_valid_guess[i]).collect();
 
-        Self {
+        Ok(Self {
+            word_len,
+            names,
             all_ans,
             all_guess,
             is_valid_ans,
             is_valid_guess,
+            weights: None,
+        })
+    }
+
+    // 答えと宣言を絞り込む. allow の名前は答え・宣言の範囲にあり, 文字数が合わなければならない.
+    // 残りの答えが少ないときは答えの候補から宣言するので, 答えは全て宣言にも使えなければならない.
+    pub fn restrict(
+        &mut self,
+        words: &WordList,
+        answers: &WordFilter,
+        guesses: &WordFilter,
+    ) -> Result<(), String> {
+        for i in answers.allow.iter().flatten() {
+            if !self.is_valid_ans.get(*i).copied().unwrap_or(false) {
+                return Err(format!(
+                    "{} is not an answer of {} letters within the answer range",
+                    words.names[*i], self.word_len
+                ));
+            }
+        }
+        for i in guesses.allow.iter().flatten() {
+            if !self.is_valid_guess.get(*i).copied().unwrap_or(false) {
+                return Err(format!(
+                    "{} is not a guess of at most {} letters within the guess range",
+                    words.names[*i], self.word_len
+                ));
+            }
+        }
+
+        answers.apply(&mut self.is_valid_ans);
+        guesses.apply(&mut self.is_valid_guess);
+        let (is_valid_ans, is_valid_guess) = (&self.is_valid_ans, &self.is_valid_guess);
+        self.all_ans.retain(|i| is_valid_ans[*i]);
+        self.all_guess.retain(|i| is_valid_guess[*i]);
+        if self.all_ans.is_empty() {
+            return Err("no answers are left".to_string());
+        }
+
+        if let Some(ans) = self
+            .all_ans
+            .iter()
+            .find(|ans| !self.is_valid_guess.get(**ans).copied().unwrap_or(false))
+        {
+            return Err(format!(
+                "the answer {} is not allowed as a guess",
+                self.names[*ans]
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn weight(&self, ans: Answer) -> usize {
+        self.weights.as_ref().map_or(1, |w| w[ans])
+    }
+
+    // ハードモードで宣言できるポケモン.
+    pub fn hard_mode_guesses(
+        &self,
+        judge_table: &JudgeTable,
+        history: &[(Guess, Judge)],
+    ) -> Vec<Guess> {
+        if history.is_empty() {
+            return self.all_guess.clone();
         }
+        self.all_guess
+            .iter()
+            .filter(|guess| judge_table.is_consistent(guess, history))
+            .copied()
+            .collect()
     }
 }
 
+// POKEMONS の各世代の終わり. ビクティニはゲームに合わせて DP までに含める.
+pub const GENERATIONS: [(&str, usize); 8] = [
+    ("RG", 148),
+    ("GS", 247),
+    ("RS", 382),
+    ("DP", 489),
+    ("BW", 644),
+    ("XY", 710),
+    ("SM", 779),
+    ("SWSH", 846),
+];
+
 pub const POKEMONS: [&str; 846] = [
     "フシギダネ",
     "フシギソウ",
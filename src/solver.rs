@@ -0,0 +1,1415 @@
+use ordered_float::OrderedFloat;
+use rayon::prelude::*;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::{bundle::*, heuristic::*, judge::*, pokemon::*, tree::*, word_set::*};
+
+type SetId = usize;
+pub type Score = i64;
+
+const INFTY: Score = Score::MAX / 2;
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Objective {
+    // 期待回数を最小化する.
+    #[default]
+    Average,
+    // 最悪回数を最小化し, 同じなら期待回数を最小化する.
+    Worst,
+}
+impl FromStr for Objective {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "average" => Ok(Objective::Average),
+            "worst" => Ok(Objective::Worst),
+            _ => Err(format!(
+                "unknown objective: {} (expected average or worst)",
+                s
+            )),
+        }
+    }
+}
+
+// シャードを選ぶだけなので, SipHash より軽いハッシュを使う.
+#[derive(Default)]
+struct ShardHasher(u64);
+impl Hasher for ShardHasher {
+    fn finish(&self) -> u64 {
+        self.0 >> 32
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.write_u64(*b as u64);
+        }
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+// キーのハッシュで振り分けた複数の HashMap. スレッド間のロックの競合を減らす.
+struct Sharded<K, V> {
+    shards: Vec<Mutex<HashMap<K, V>>>,
+}
+impl<K, V> Sharded<K, V> {
+    const NUM_SHARDS: usize = 64;
+}
+impl<K: Hash + Eq, V: Clone> Sharded<K, V> {
+    fn shard(&self, key: &K) -> MutexGuard<'_, HashMap<K, V>> {
+        let mut hasher = ShardHasher::default();
+        key.hash(&mut hasher);
+        self.shards[hasher.finish() as usize % Self::NUM_SHARDS]
+            .lock()
+            .unwrap()
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.shard(key).get(key).cloned()
+    }
+
+    // 値を clone せずに f に渡す.
+    pub fn get_with<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.shard(key).get(key).map(f)
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.shard(&key).insert(key, value);
+    }
+
+    // key がなければ f() の値を入れる. f はロックを取ったまま呼ばれる.
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> V {
+        self.shard(&key).entry(key).or_insert_with(f).clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().unwrap().len()).sum()
+    }
+}
+impl<K, V> Default for Sharded<K, V> {
+    fn default() -> Self {
+        Self {
+            shards: (0..Self::NUM_SHARDS)
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+}
+impl<K: Serialize + Clone, V: Serialize + Clone> Serialize for Sharded<K, V> {
+    // シャードを 1 つずつロックして複製し, 書き出しはロックを離してから行う.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let shards: Vec<HashMap<K, V>> = self
+            .shards
+            .iter()
+            .map(|s| s.lock().unwrap().clone())
+            .collect();
+        let mut map = serializer.serialize_map(Some(shards.iter().map(|s| s.len()).sum()))?;
+        for (key, value) in shards.iter().flat_map(|s| s.iter()) {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+impl<'de, K, V> Deserialize<'de> for Sharded<K, V>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de> + Clone,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ret = Self::default();
+        for (key, value) in HashMap::<K, V>::deserialize(deserializer)? {
+            ret.insert(key, value);
+        }
+        Ok(ret)
+    }
+}
+
+// チェックポイントはフィールドの順に, 各フィールドをシャードごとにロックして複製してから書き出す.
+// 一度に持つロックは 1 つで, ファイルへの書き込み中はロックを持たないので, 保存の間も探索は進む.
+// そのため複製した時点はフィールドやシャードごとにずれる. それでも best[X] は memo[X] に最適解の宣言を
+// 入れた後に入れるので, best を memo より先に複製すれば, 複製した best[X] に対応する memo[X] は
+// その後に複製されて最適解の宣言になっている.
+// 番号を振る set_id, state_id, cnt を最後に置くと, memo などに現れる番号は必ず set_id か state_id に含まれる.
+#[derive(Default, Serialize, Deserialize)]
+struct Cache {
+    best: Sharded<SetId, Score>,
+    // 宣言だけを持ち, 分割は決定木を作るときに計算し直す.
+    // 分割の 1 つの部分は (Judge, WordSet) で 136 バイトあり, 分割ごと持つとメモが何十倍にもなる.
+    memo: Sharded<SetId, (Score, Guess)>,
+    lb_memo: Sharded<SetId, (usize, Score)>,
+
+    lb_worst_memo: Sharded<SetId, (usize, Score)>,
+
+    // 最悪回数を最小化する場合の, 根での宣言回数の上限.
+    depth_limit: Mutex<Option<Score>>,
+
+    set_id: Sharded<WordSet, SetId>,
+    state_id: Sharded<(SetId, Option<SetId>, Option<Score>), SetId>,
+    cnt: AtomicUsize,
+}
+impl Cache {
+    pub fn save(&self, filepath: &str, config: &Config) -> Result<(), bincode::Error> {
+        // 書き込み中に止まっても前のチェックポイントが壊れないように, 一時ファイルから置き換える.
+        let tmp = format!("{}.tmp", filepath);
+        let f = io::BufWriter::new(fs::File::create(&tmp)?);
+        bincode::serialize_into(f, &(config, self))?;
+        fs::rename(&tmp, filepath)?;
+        Ok(())
+    }
+
+    pub fn load(filepath: &str, config: &Config) -> Result<Self, String> {
+        let f = io::BufReader::new(fs::File::open(filepath).map_err(|e| e.to_string())?);
+        let (saved, cache): (Config, Cache) =
+            bincode::deserialize_from(f).map_err(|e| e.to_string())?;
+        if saved != *config {
+            return Err(format!(
+                "the checkpoint was made with different settings: {}",
+                saved
+            ));
+        }
+        Ok(cache)
+    }
+
+    fn new_id(&self) -> SetId {
+        self.cnt.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn get_set_id(&self, st: &WordSet) -> SetId {
+        self.set_id.get_or_insert_with(*st, || self.new_id())
+    }
+
+    // ハードモードでは宣言できるポケモンの集合を, 宣言回数に上限がある場合は上限を,
+    // 残りの答えの集合と合わせて状態として扱う.
+    pub fn get_state_id(
+        &self,
+        st: &WordSet,
+        guesses: Option<&WordSet>,
+        limit: Option<Score>,
+    ) -> SetId {
+        let set_id = self.get_set_id(st);
+        if guesses.is_none() && limit.is_none() {
+            return set_id;
+        }
+        let key = (set_id, guesses.map(|g| self.get_set_id(g)), limit);
+        self.state_id.get_or_insert_with(key, || self.new_id())
+    }
+}
+
+// チェックポイントが同じ問題を解いていたときのものか確かめるための設定.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct Config {
+    names: Vec<String>,
+    word_len: usize,
+    ans_until: usize,
+    guess_until: usize,
+    objective: Objective,
+    hard: bool,
+    weights: Option<Vec<usize>>,
+    answers: Vec<Answer>,
+    guesses: Vec<Guess>,
+}
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} words ({} answers, {} guesses), --word-len {} --ans-until {} --guess-until {} --objective {}{}{}",
+            self.names.len(),
+            self.answers.len(),
+            self.guesses.len(),
+            self.word_len,
+            self.ans_until,
+            self.guess_until,
+            match self.objective {
+                Objective::Average => "average",
+                Objective::Worst => "worst",
+            },
+            if self.hard { " --hard" } else { "" },
+            if self.weights.is_some() {
+                " --weights"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+// 枝刈りの統計.
+#[derive(Default)]
+struct Stats {
+    // dfs_best_solution で探索した状態の数.
+    nodes: AtomicUsize,
+    // 状態の下界が上界以上で枝刈りした数.
+    pruned_nodes: AtomicUsize,
+    // 宣言ごとの下界が上界以上で枝刈りした数.
+    pruned_guesses: AtomicUsize,
+
+    best_lookups: AtomicUsize,
+    best_hits: AtomicUsize,
+    lb_lookups: AtomicUsize,
+    lb_hits: AtomicUsize,
+
+    // 根で調べ終えた宣言の数, 根の宣言の数, 根での今の最良値.
+    root_done: AtomicUsize,
+    root_total: AtomicUsize,
+    root_best: AtomicI64,
+}
+
+// 探索の途中経過. --progress-json では 1 行ずつ JSON で書き出す.
+#[derive(Serialize)]
+pub struct Progress {
+    pub elapsed: f64,
+    pub depth_limit: Option<Score>,
+    pub root_done: usize,
+    pub root_total: usize,
+    pub root_best: Option<Score>,
+    pub nodes: usize,
+    pub pruned_nodes: usize,
+    pub pruned_guesses: usize,
+    pub best_hit_rate: f64,
+    pub lb_hit_rate: f64,
+    pub eta: Option<f64>,
+}
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{:.1}s]", self.elapsed)?;
+        if let Some(limit) = self.depth_limit {
+            write!(f, " limit {},", limit)?;
+        }
+        write!(f, " root {}/{}", self.root_done, self.root_total)?;
+        if let Some(best) = self.root_best {
+            write!(f, ", best {}", best)?;
+        }
+        write!(
+            f,
+            ", nodes {} (pruned {}), guesses pruned {}, hit rate best {:.3} lb {:.3}",
+            self.nodes,
+            self.pruned_nodes,
+            self.pruned_guesses,
+            self.best_hit_rate,
+            self.lb_hit_rate
+        )?;
+        if let Some(eta) = self.eta {
+            write!(f, ", eta {:.0}s", eta)?;
+        }
+        Ok(())
+    }
+}
+
+// 探索の結果. total は書き出す決定木の宣言回数の合計 (答えに重みを付けた場合は重み付きの合計).
+pub struct Solution {
+    pub total: Score,
+    // 期待回数の合計の下界. 打ち切らなければ total と等しい.
+    pub lower_bound: Score,
+    // 最悪回数を最小化した場合の最悪回数. 打ち切って上限を満たす決定木がなければ None.
+    pub worst: Option<Score>,
+    // 最悪回数を最小化した場合の最悪回数の下界.
+    pub worst_lower_bound: Option<Score>,
+    pub aborted: bool,
+}
+
+// Solver の設定. new() の後に必要なものだけ変えて build() する.
+#[derive(Clone)]
+pub struct SolverBuilder {
+    word_len: usize,
+    ans_until: Option<usize>,
+    guess_until: Option<usize>,
+    objective: Objective,
+    hard: bool,
+    lb_depth: usize,
+    time_limit: Option<Duration>,
+    node_limit: Option<usize>,
+    greedy_heuristic: Heuristic,
+    order_heuristic: Heuristic,
+    weights: Option<Vec<usize>>,
+    answer_filter: WordFilter,
+    guess_filter: WordFilter,
+}
+impl Default for SolverBuilder {
+    fn default() -> Self {
+        Self {
+            word_len: WORD_LEN,
+            ans_until: None,
+            guess_until: None,
+            objective: Objective::default(),
+            hard: false,
+            lb_depth: 1,
+            time_limit: None,
+            node_limit: None,
+            greedy_heuristic: Heuristic::new("weight:0.1", Weighted(0.1)),
+            order_heuristic: Heuristic::new("entropy", Entropy),
+            weights: None,
+            answer_filter: WordFilter::default(),
+            guess_filter: WordFilter::default(),
+        }
+    }
+}
+impl SolverBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn word_len(mut self, word_len: usize) -> Self {
+        self.word_len = word_len;
+        self
+    }
+
+    // 答えにする単語の数. 指定しなければ単語リスト全体.
+    pub fn ans_until(mut self, ans_until: usize) -> Self {
+        self.ans_until = Some(ans_until);
+        self
+    }
+
+    // 宣言できる単語の数. 指定しなければ単語リスト全体.
+    pub fn guess_until(mut self, guess_until: usize) -> Self {
+        self.guess_until = Some(guess_until);
+        self
+    }
+
+    pub fn objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    pub fn hard(mut self, hard: bool) -> Self {
+        self.hard = hard;
+        self
+    }
+
+    pub fn lb_depth(mut self, lb_depth: usize) -> Self {
+        self.lb_depth = lb_depth;
+        self
+    }
+
+    // solve() を呼んでからの時間の上限.
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = Some(node_limit);
+        self
+    }
+
+    // 貪欲解で宣言を選ぶ評価.
+    pub fn greedy_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.greedy_heuristic = heuristic;
+        self
+    }
+
+    // 分枝限定法で宣言を調べる順番を決める評価.
+    pub fn order_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.order_heuristic = heuristic;
+        self
+    }
+
+    // 単語の番号ごとの答えの重み (WordList::load_weights で読む). 期待回数は重み付きで数える.
+    pub fn weights(mut self, weights: Vec<usize>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    // 答えにするポケモンを名前の一覧で絞り込む.
+    pub fn answer_filter(mut self, filter: WordFilter) -> Self {
+        self.answer_filter = filter;
+        self
+    }
+
+    // 宣言できるポケモンを名前の一覧で絞り込む.
+    pub fn guess_filter(mut self, filter: WordFilter) -> Self {
+        self.guess_filter = filter;
+        self
+    }
+
+    // 答えや宣言の範囲・絞り込み・重みが正しくなければエラーを返す.
+    pub fn build(&self, words: &WordList) -> Result<Solver, String> {
+        Solver::new(words, self)
+    }
+}
+
+pub struct Solver {
+    ans_until: usize,
+    guess_until: usize,
+    pokemons: PokemonList,
+    judge_table: JudgeTable,
+    all_ans: WordSet,
+    objective: Objective,
+    hard: bool,
+
+    // 下界を計算するときに先読みする宣言の数.
+    lb_depth: usize,
+    // 残りの答えの数ごとの, 先読みしない場合の下界.
+    lb_table: Vec<Score>,
+    lb_worst_table: Vec<Score>,
+
+    greedy_heuristic: Heuristic,
+    order_heuristic: Heuristic,
+
+    cache: Cache,
+    stats: Stats,
+
+    // 探索を打ち切るまでの時間と, dfs_best_solution で探索する状態の数の上限.
+    time_limit: Option<Duration>,
+    deadline: OnceLock<Instant>,
+    node_limit: Option<usize>,
+    aborted: AtomicBool,
+}
+
+impl Solver {
+    fn new(words: &WordList, builder: &SolverBuilder) -> Result<Self, String> {
+        let word_len = builder.word_len;
+        let ans_until = builder.ans_until.unwrap_or(words.len());
+        let guess_until = builder.guess_until.unwrap_or(words.len());
+        let hard = builder.hard;
+
+        let mut pokemons = PokemonList::from_words(words, word_len, ans_until, guess_until)?;
+        pokemons.restrict(words, &builder.answer_filter, &builder.guess_filter)?;
+        // 答えの集合 (ハードモードでは宣言の集合も) を, 一覧での位置の WordSet で持つ.
+        if pokemons.all_ans.len() > WordSet::CAPACITY
+            || (hard && pokemons.all_guess.len() > WordSet::CAPACITY)
+        {
+            return Err(format!(
+                "at most {} answers (and guesses in hard mode) are supported",
+                WordSet::CAPACITY
+            ));
+        }
+        if let Some(weights) = &builder.weights {
+            if weights.len() < pokemons.names.len() {
+                return Err(format!(
+                    "expected weights of {} pokemons, found {}",
+                    pokemons.names.len(),
+                    weights.len()
+                ));
+            }
+            // 重みが 0 の答えがあると rem が 0 の節点ができる.
+            if let Some(ans) = pokemons.all_ans.iter().find(|ans| weights[**ans] == 0) {
+                return Err(format!(
+                    "the weight of {} must be positive",
+                    pokemons.names[*ans]
+                ));
+            }
+            pokemons.weights = Some(weights[..pokemons.names.len()].to_vec());
+        }
+        let judge_table = JudgeTable::new(&pokemons);
+        let all_ans = WordSet::from_slice(&(0..pokemons.all_ans.len()).collect::<Vec<_>>());
+
+        // 宣言回数は答えの数を超えないので, 重み付きの合計は 重みの和 * 答えの数 以下.
+        let total_weight = pokemons
+            .all_ans
+            .iter()
+            .fold(0usize, |sum, a| sum.saturating_add(pokemons.weight(*a)));
+        if total_weight.saturating_mul(all_ans.len()) >= INFTY as usize {
+            return Err(format!(
+                "the weights are too large: the sum {} times {} answers must be less than {}",
+                total_weight,
+                all_ans.len(),
+                INFTY
+            ));
+        }
+
+        // 1 回の宣言で答えの集合は高々 k 個に分かれる (当たりを除く).
+        // 決定木の深さ d の節点は高々 k^(d-1) 個で, 各節点で当たる答えは高々 1 つなので,
+        // 浅い方から順に詰めたものが下界になる.
+        let k = pokemons
+            .all_guess
+            .iter()
+            .map(|guess| {
+                judge_table
+                    .partition_set(&all_ans, guess, &pokemons.all_ans)
+                    .len()
+            })
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let (mut lb_table, mut lb_worst_table) = (vec![0], vec![0]);
+        let (mut depth, mut cap, mut used) = (1, 1usize, 0);
+        for n in 1..=all_ans.len() {
+            if used == cap {
+                depth += 1;
+                cap = cap.saturating_mul(k);
+                used = 0;
+            }
+            used += 1;
+            lb_table.push(lb_table[n - 1] + depth);
+            lb_worst_table.push(depth);
+        }
+
+        Ok(Self {
+            ans_until,
+            guess_until,
+            pokemons,
+            judge_table,
+            all_ans,
+            objective: builder.objective,
+            hard,
+            lb_depth: builder.lb_depth,
+            lb_table,
+            lb_worst_table,
+            greedy_heuristic: builder.greedy_heuristic.clone(),
+            order_heuristic: builder.order_heuristic.clone(),
+            cache: Cache::default(),
+            stats: Stats::default(),
+            time_limit: builder.time_limit,
+            deadline: OnceLock::new(),
+            node_limit: builder.node_limit,
+            aborted: AtomicBool::new(false),
+        })
+    }
+
+    // 答えにするポケモンの数.
+    pub fn num_answers(&self) -> usize {
+        self.all_ans.len()
+    }
+
+    // 答えの重みの和. 重みがなければ答えの数.
+    pub fn total_weight(&self) -> Score {
+        self.weight(&self.all_ans)
+    }
+
+    // rem_ans の答え. WordSet には答えの一覧 pokemons.all_ans での位置が入っている.
+    fn answers(&self, rem_ans: &WordSet) -> Vec<Answer> {
+        rem_ans.iter().map(|i| self.pokemons.all_ans[i]).collect()
+    }
+
+    fn contains(&self, rem_ans: &WordSet, ans: &Answer) -> bool {
+        self.pokemons
+            .all_ans
+            .binary_search(ans)
+            .is_ok_and(|i| rem_ans.contains(i))
+    }
+
+    fn partition(&self, rem_ans: &WordSet, guess: &Guess) -> SetPartition {
+        self.judge_table
+            .partition_set(rem_ans, guess, &self.pokemons.all_ans)
+    }
+
+    // ハードモードで宣言できるポケモンの集合. 宣言の一覧 pokemons.all_guess での位置を入れる.
+    fn guess_set(&self, rem_guess: &[Guess]) -> WordSet {
+        let mut ret = WordSet::default();
+        for guess in rem_guess {
+            ret.insert(self.pokemons.all_guess.binary_search(guess).unwrap());
+        }
+        ret
+    }
+
+    fn weight(&self, rem_ans: &WordSet) -> Score {
+        match &self.pokemons.weights {
+            Some(weights) => rem_ans
+                .iter()
+                .map(|i| weights[self.pokemons.all_ans[i]] as Score)
+                .sum(),
+            None => rem_ans.len() as Score,
+        }
+    }
+
+    // 残りが 2 つ以下の場合の宣言の順番. 重い方から宣言する.
+    fn small_order(&self, rem_ans: &WordSet) -> Vec<Answer> {
+        let mut order = self.answers(rem_ans);
+        order.sort_by_key(|ans| std::cmp::Reverse(self.pokemons.weight(*ans)));
+        order
+    }
+
+    // 残りが 2 つ以下の場合の宣言回数の合計.
+    fn small_total(&self, rem_ans: &WordSet) -> Score {
+        self.small_order(rem_ans)
+            .iter()
+            .enumerate()
+            .map(|(i, ans)| (i + 1) as Score * self.pokemons.weight(*ans) as Score)
+            .sum()
+    }
+
+    // 先読みしない場合の下界. 重みがあれば, 重い答えから順に浅い節点に詰める.
+    fn base_lower_bound(&self, rem_ans: &WordSet) -> Score {
+        let weights = match &self.pokemons.weights {
+            Some(weights) => weights,
+            None => return self.lb_table[rem_ans.len()],
+        };
+        let mut sorted: Vec<usize> = rem_ans
+            .iter()
+            .map(|i| weights[self.pokemons.all_ans[i]])
+            .collect();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        sorted
+            .iter()
+            .enumerate()
+            .map(|(i, w)| *w as Score * (self.lb_table[i + 1] - self.lb_table[i]))
+            .sum()
+    }
+
+    pub fn save_checkpoint(&self, filepath: &str) -> Result<(), String> {
+        self.cache
+            .save(filepath, &self.config())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn resume(&mut self, filepath: &str) -> Result<(), String> {
+        self.cache = Cache::load(filepath, &self.config())?;
+        Ok(())
+    }
+
+    // 表示用に各メモの大きさを返す.
+    pub fn cache_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("best", self.cache.best.len()),
+            ("memo", self.cache.memo.len()),
+            ("lb_memo", self.cache.lb_memo.len()),
+        ]
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            names: self.pokemons.names.clone(),
+            word_len: self.pokemons.word_len,
+            ans_until: self.ans_until,
+            guess_until: self.guess_until,
+            objective: self.objective,
+            hard: self.hard,
+            weights: self.pokemons.weights.clone(),
+            answers: self.pokemons.all_ans.clone(),
+            guesses: self.pokemons.all_guess.clone(),
+        }
+    }
+
+    pub fn progress(&self, elapsed: f64) -> Progress {
+        let load = |x: &AtomicUsize| x.load(Ordering::Relaxed);
+        let rate = |hits: &AtomicUsize, lookups: &AtomicUsize| {
+            load(hits) as f64 / load(lookups).max(1) as f64
+        };
+        let (root_done, root_total) = (load(&self.stats.root_done), load(&self.stats.root_total));
+        let root_best = self.stats.root_best.load(Ordering::Relaxed);
+        Progress {
+            elapsed,
+            depth_limit: *self.cache.depth_limit.lock().unwrap(),
+            root_done,
+            root_total,
+            root_best: if root_best > 0 && root_best < INFTY {
+                Some(root_best)
+            } else {
+                None
+            },
+            nodes: load(&self.stats.nodes),
+            pruned_nodes: load(&self.stats.pruned_nodes),
+            pruned_guesses: load(&self.stats.pruned_guesses),
+            best_hit_rate: rate(&self.stats.best_hits, &self.stats.best_lookups),
+            lb_hit_rate: rate(&self.stats.lb_hits, &self.stats.lb_lookups),
+            // 根の宣言ごとの時間が同じだと見なした, 大まかな残り時間.
+            eta: if root_done > 0 {
+                Some(elapsed * (root_total - root_done) as f64 / root_done as f64)
+            } else {
+                None
+            },
+        }
+    }
+
+    fn get_state_id(&self, rem_ans: &WordSet, rem_guess: &[Guess], limit: Option<Score>) -> SetId {
+        let guesses = if self.hard {
+            Some(self.guess_set(rem_guess))
+        } else {
+            None
+        };
+        self.cache.get_state_id(rem_ans, guesses.as_ref(), limit)
+    }
+
+    // ハードモードでは, 次に宣言できるのは (guess, judge) と矛盾しないポケモンだけ.
+    fn next_guesses(
+        &self,
+        rem_guess: &[Guess],
+        guess: &Guess,
+        judge: &Judge,
+    ) -> Option<Vec<Guess>> {
+        if !self.hard {
+            return None;
+        }
+        Some(
+            rem_guess
+                .iter()
+                .filter(|g| self.judge_table.is_consistent(g, &[(*guess, *judge)]))
+                .copied()
+                .collect(),
+        )
+    }
+
+    // 貪欲に宣言を選んだ決定木を作り, 宣言回数の合計を返す. 決定木は build_node() か write() で取り出す.
+    pub fn solve_greedy(&self) -> Score {
+        self.dfs_good_solution(&self.all_ans, &self.pokemons.all_guess)
+    }
+
+    fn dfs_good_solution(&self, rem_ans: &WordSet, rem_guess: &Vec<Guess>) -> Score {
+        assert!(!rem_ans.is_empty());
+        if rem_ans.len() <= 2 {
+            return self.small_total(rem_ans);
+        }
+
+        let rem_id = self.get_state_id(rem_ans, rem_guess, None);
+
+        if let Some(val) = self.cache.memo.get_with(&rem_id, |(val, ..)| *val) {
+            return val;
+        }
+
+        let cands;
+        let all_guess = if rem_ans.len() == 3 {
+            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
+            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+
+            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
+            cands = self.answers(rem_ans);
+            &cands
+        } else {
+            rem_guess
+        };
+
+        let good_guess = all_guess
+            .par_iter()
+            .min_by_key(|guess| {
+                let part = self.partition(rem_ans, guess);
+                OrderedFloat(
+                    self.greedy_heuristic
+                        .penalty(part.iter().map(|(_, s)| self.weight(s) as usize)),
+                )
+            })
+            .unwrap();
+
+        // TODO: avoid same calculation
+        let part = self.partition(rem_ans, good_guess);
+
+        let val: Score = self.weight(rem_ans)
+            + part
+                .par_iter()
+                .map(|(judge, s)| {
+                    let next = self.next_guesses(rem_guess, good_guess, judge);
+                    self.dfs_good_solution(s, next.as_ref().unwrap_or(rem_guess))
+                })
+                .sum::<Score>();
+
+        self.cache.memo.insert(rem_id, (val, *good_guess));
+
+        val
+    }
+
+    // ハードモードでも全ての宣言を許した緩和問題の下界を使う.
+    fn lower_bound(&self, rem_ans: &WordSet, depth: usize) -> Score {
+        assert!(!rem_ans.is_empty());
+        if depth == 0 || rem_ans.len() <= 2 {
+            return self.base_lower_bound(rem_ans);
+        }
+
+        let rem_id = self.cache.get_set_id(rem_ans);
+
+        self.stats.lb_lookups.fetch_add(1, Ordering::Relaxed);
+        if let Some((d, lb)) = self.cache.lb_memo.get(&rem_id) {
+            if d >= depth {
+                self.stats.lb_hits.fetch_add(1, Ordering::Relaxed);
+                return lb;
+            }
+        }
+
+        let cands;
+        let all_guess = if rem_ans.len() == 3 {
+            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
+            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+
+            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
+            cands = self.answers(rem_ans);
+            &cands
+        } else {
+            &self.pokemons.all_guess
+        };
+
+        let ret: Score = self.weight(rem_ans)
+            + all_guess
+                .par_iter()
+                .map(|guess| {
+                    self.partition(rem_ans, guess)
+                        .iter()
+                        .map(|(_, s)| self.lower_bound(s, depth - 1))
+                        .sum::<Score>()
+                })
+                .min()
+                .unwrap();
+
+        // assert!(ret >= 2 * rem_ans.len() as Score - 1);
+
+        self.cache.lb_memo.insert(rem_id, (depth, ret));
+        ret
+    }
+
+    // 最適な決定木を探す. 決定木は build_node() か write() で取り出す.
+    pub fn solve(&self) -> Solution {
+        if let Some(time_limit) = self.time_limit {
+            self.deadline.get_or_init(|| Instant::now() + time_limit);
+        }
+        if self.objective == Objective::Worst {
+            return self.build_minmax_solution();
+        }
+        // 最初の状態を展開する前に打ち切っても書き出せるように, 根の貪欲解を先に作っておく.
+        self.dfs_good_solution(&self.all_ans, &self.pokemons.all_guess);
+        let total = self.dfs_best_solution(&self.all_ans, &self.pokemons.all_guess, INFTY, None, 0);
+        if !self.is_aborted() {
+            return Solution {
+                total,
+                lower_bound: total,
+                worst: None,
+                worst_lower_bound: None,
+                aborted: false,
+            };
+        }
+
+        // 打ち切った後も部分木の解は良くなりうるので, 書き出す決定木から数え直す.
+        Solution {
+            total: self.memo_total(&self.all_ans, &self.pokemons.all_guess, None),
+            lower_bound: self.lower_bound(&self.all_ans, self.lb_depth),
+            worst: None,
+            worst_lower_bound: None,
+            aborted: true,
+        }
+    }
+
+    fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    // 時間か状態の数の上限を超えたら, 以降の探索を打ち切る.
+    fn out_of_budget(&self) -> bool {
+        if self.is_aborted() {
+            return true;
+        }
+        let over = self.deadline.get().is_some_and(|d| Instant::now() >= *d)
+            || self
+                .node_limit
+                .is_some_and(|n| self.stats.nodes.load(Ordering::Relaxed) >= n);
+        if over {
+            self.aborted.store(true, Ordering::Relaxed);
+        }
+        over
+    }
+
+    // limit が与えられた場合, 宣言回数が limit 以下の決定木に限って期待回数を最小化する.
+    // depth は根からの宣言回数で, 根では進み具合を記録する.
+    fn dfs_best_solution(
+        &self,
+        rem_ans: &WordSet,
+        rem_guess: &Vec<Guess>,
+        ub: Score,
+        limit: Option<Score>,
+        depth: usize,
+    ) -> Score {
+        assert!(!rem_ans.is_empty());
+        if rem_ans.len() == 2 && limit == Some(1) {
+            return INFTY;
+        }
+        if rem_ans.len() <= 2 {
+            return self.small_total(rem_ans);
+        }
+
+        let rem_id = self.get_state_id(rem_ans, rem_guess, limit);
+
+        self.stats.best_lookups.fetch_add(1, Ordering::Relaxed);
+        if let Some(val) = self.cache.best.get(&rem_id) {
+            self.stats.best_hits.fetch_add(1, Ordering::Relaxed);
+            return val;
+        }
+        if self.out_of_budget() {
+            return INFTY;
+        }
+        self.stats.nodes.fetch_add(1, Ordering::Relaxed);
+
+        if self.lower_bound(rem_ans, self.lb_depth) >= ub {
+            self.stats.pruned_nodes.fetch_add(1, Ordering::Relaxed);
+            return INFTY;
+        }
+        if let Some(limit) = limit {
+            if self.lower_bound_worst(rem_ans, self.lb_depth) > limit {
+                self.stats.pruned_nodes.fetch_add(1, Ordering::Relaxed);
+                return INFTY;
+            }
+        }
+
+        // 上限がある場合は貪欲解が上限を超えうるので, ub より良い解だけを探す.
+        let mut val = match limit {
+            Some(_) => ub,
+            None => self.dfs_good_solution(rem_ans, rem_guess),
+        };
+
+        let cands;
+        let all_guess = if rem_ans.len() == 3 && limit.is_none() {
+            // 残りの候補から宣言した場合, 最悪 1 + 2 + 3 = 6
+            // 他の候補を宣言した場合, 最良で 2 * 3 = 6
+
+            // 従って残りの3つの候補から宣言する場合だけ考えれば良い.
+            // (宣言回数に上限がある場合は, 候補外の宣言で 2 回に抑える必要がありうる.)
+            cands = self.answers(rem_ans);
+            &cands
+        } else {
+            rem_guess
+        };
+
+        let partitions: Vec<SetPartition> = all_guess
+            .par_iter()
+            .map(|guess| self.partition(rem_ans, guess))
+            .collect();
+
+        let penalty: Vec<f32> = partitions
+            .par_iter()
+            .map(|part| {
+                self.order_heuristic
+                    .penalty(part.iter().map(|(_, s)| self.weight(s) as usize))
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..all_guess.len()).collect();
+        order.sort_by_key(|i| OrderedFloat(penalty[*i]));
+
+        let is_root = depth == 0;
+        if is_root {
+            self.stats.root_total.store(order.len(), Ordering::Relaxed);
+            self.stats.root_best.store(val, Ordering::Relaxed);
+        }
+
+        for (k, &i) in order.iter().enumerate() {
+            if self.out_of_budget() {
+                break;
+            }
+            if is_root {
+                self.stats.root_done.store(k, Ordering::Relaxed);
+            }
+            let guess = &all_guess[i];
+            let part = &partitions[i];
+
+            let lb = self.weight(rem_ans)
+                + part
+                    .iter()
+                    .map(|(_, s)| self.lower_bound(s, self.lb_depth))
+                    .sum::<Score>();
+
+            // // ここを並列化すると遅くなる.
+            // // parallel
+            // let lb: Score = rem_ans.len() as Score + part.par_iter().map(|(_, s)| {
+            //     self.lower_bound(s, self.lb_depth)
+            // }).sum::<Score>();
+
+            if lb >= val {
+                self.stats.pruned_guesses.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            if let Some(limit) = limit {
+                let lb_worst = 1 + part
+                    .iter()
+                    .map(|(_, s)| self.lower_bound_worst(s, self.lb_depth))
+                    .max()
+                    .unwrap_or(0);
+                if lb_worst > limit {
+                    self.stats.pruned_guesses.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+
+            let mut tmp = self.weight(rem_ans);
+            for (judge, s) in part.iter() {
+                let next = self.next_guesses(rem_guess, guess, judge);
+                tmp += self.dfs_best_solution(
+                    s,
+                    next.as_ref().unwrap_or(rem_guess),
+                    val - tmp,
+                    limit.map(|d| d - 1),
+                    depth + 1,
+                );
+                if tmp >= val {
+                    break;
+                }
+            }
+
+            if tmp < val {
+                val = tmp;
+                self.cache.memo.insert(rem_id, (val, *guess));
+                if is_root {
+                    self.stats.root_best.store(val, Ordering::Relaxed);
+                }
+            }
+        }
+        if is_root {
+            self.stats.root_done.store(order.len(), Ordering::Relaxed);
+        }
+
+        if limit.is_some() && val >= ub && ub < INFTY {
+            // ub 以上であることしか分からないので, メモしない.
+            return INFTY;
+        }
+        if self.is_aborted() {
+            // 打ち切った場合, val は見つかった中で最良の値で, 最適とは限らないのでメモしない.
+            return val;
+        }
+
+        self.cache.best.insert(rem_id, val);
+
+        val
+    }
+
+    fn build_minmax_solution(&self) -> Solution {
+        // 最悪回数の下界から順に, その回数以下で全ての答えを当てられるか調べる.
+        let mut limit = self.lower_bound_worst(&self.all_ans, self.lb_depth);
+        let total = loop {
+            *self.cache.depth_limit.lock().unwrap() = Some(limit);
+            let total = self.dfs_best_solution(
+                &self.all_ans,
+                &self.pokemons.all_guess,
+                INFTY,
+                Some(limit),
+                0,
+            );
+            if total < INFTY || self.is_aborted() {
+                break total;
+            }
+            limit += 1;
+        };
+
+        if !self.is_aborted() {
+            return Solution {
+                total,
+                lower_bound: total,
+                worst: Some(limit),
+                worst_lower_bound: Some(limit),
+                aborted: false,
+            };
+        }
+
+        let lower_bound = self.lower_bound(&self.all_ans, self.lb_depth);
+        if total < INFTY {
+            Solution {
+                total: self.memo_total(&self.all_ans, &self.pokemons.all_guess, Some(limit)),
+                lower_bound,
+                worst: Some(limit),
+                worst_lower_bound: Some(limit),
+                aborted: true,
+            }
+        } else {
+            // 上限を満たす決定木が見つかっていないので, 貪欲解を使う.
+            *self.cache.depth_limit.lock().unwrap() = None;
+            self.dfs_good_solution(&self.all_ans, &self.pokemons.all_guess);
+            Solution {
+                total: self.memo_total(&self.all_ans, &self.pokemons.all_guess, None),
+                lower_bound,
+                worst: None,
+                worst_lower_bound: Some(limit),
+                aborted: true,
+            }
+        }
+    }
+
+    // lower_bound と同様に, ハードモードでも全ての宣言を許す.
+    fn lower_bound_worst(&self, rem_ans: &WordSet, depth: usize) -> Score {
+        assert!(!rem_ans.is_empty());
+        if rem_ans.len() == 1 {
+            return 1;
+        }
+        if depth == 0 || rem_ans.len() == 2 {
+            return self.lb_worst_table[rem_ans.len()];
+        }
+
+        let rem_id = self.cache.get_set_id(rem_ans);
+
+        if let Some((d, lb)) = self.cache.lb_worst_memo.get(&rem_id) {
+            if d >= depth {
+                return lb;
+            }
+        }
+
+        // 最悪回数では残りが3つでも候補外の宣言が真に良い場合があるので, 全ての宣言を考える.
+        let ret: Score = 1 + self
+            .pokemons
+            .all_guess
+            .par_iter()
+            .map(|guess| {
+                self.partition(rem_ans, guess)
+                    .iter()
+                    .map(|(_, s)| self.lower_bound_worst(s, depth - 1))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .min()
+            .unwrap();
+
+        self.cache.lb_worst_memo.insert(rem_id, (depth, ret));
+        ret
+    }
+
+    // 拡張子が .json なら JSON, それ以外は宣言列のテキストとして書き出す.
+    pub fn write(&self, filepath: &str) {
+        if filepath.ends_with(".json") {
+            let mut f = fs::File::create(filepath).unwrap();
+            self.build_node()
+                .write(&mut f, &self.pokemons, &Self::JSON_OPTIONS);
+        } else {
+            self.write_guess_seq(filepath);
+        }
+    }
+
+    const JSON_OPTIONS: JsonOptions = JsonOptions {
+        answers: false,
+        score: true,
+    };
+
+    pub fn write_const_js(&self, filepath: &str, label: &str) -> io::Result<()> {
+        let json = self
+            .build_node()
+            .to_json(&self.pokemons, &Self::JSON_OPTIONS);
+        update_const_js(filepath, label, serde_json::to_string(&json)?)
+    }
+
+    pub fn build_node(&self) -> Rc<Node> {
+        let limit = *self.cache.depth_limit.lock().unwrap();
+        self.dfs_build_node(&self.all_ans, &self.pokemons.all_guess, limit)
+    }
+
+    // memo から dfs_build_guess_seq と同じ決定木を作る.
+    fn dfs_build_node(
+        &self,
+        rem_ans: &WordSet,
+        rem_guess: &Vec<Guess>,
+        limit: Option<Score>,
+    ) -> Rc<Node> {
+        let guess = if rem_ans.len() <= 2 {
+            self.small_order(rem_ans)[0]
+        } else {
+            self.memo_guess(rem_ans, rem_guess, limit)
+        };
+        let part = self.partition(rem_ans, &guess);
+
+        let mut edges: BTreeMap<Judge, Rc<Node>> = part
+            .iter()
+            .map(|(judge, s)| {
+                let next = self.next_guesses(rem_guess, &guess, judge);
+                (
+                    *judge,
+                    self.dfs_build_node(
+                        s,
+                        next.as_ref().unwrap_or(rem_guess),
+                        limit.map(|d| d - 1),
+                    ),
+                )
+            })
+            .collect();
+        if self.contains(rem_ans, &guess) {
+            edges.insert(self.judge_table.all_correct, Rc::new(Node::Terminal));
+        }
+
+        Rc::new(Node::NonTerminal {
+            guess,
+            rem_ans: self.answers(rem_ans),
+            edges,
+        })
+    }
+
+    fn write_guess_seq(&self, filepath: &str) {
+        let mut guess_seq: Vec<Vec<Guess>> = (0..self.ans_until).map(|_| Vec::new()).collect();
+        let limit = *self.cache.depth_limit.lock().unwrap();
+        self.dfs_build_guess_seq(
+            &mut guess_seq,
+            &self.all_ans,
+            &self.pokemons.all_guess,
+            limit,
+        );
+
+        let mut f = fs::File::create(filepath).unwrap();
+        for guess in &guess_seq {
+            f.write_all(
+                format!(
+                    "{}\n",
+                    guess
+                        .iter()
+                        .map(|g| g.to_string())
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        }
+    }
+
+    // memo に記録した, rem_ans で宣言するポケモン.
+    // solve() か solve_greedy() の後に, 最初に宣言するポケモン.
+    pub fn first_guess(&self) -> Guess {
+        if self.all_ans.len() <= 2 {
+            return self.small_order(&self.all_ans)[0];
+        }
+        let limit = *self.cache.depth_limit.lock().unwrap();
+        self.memo_guess(&self.all_ans, &self.pokemons.all_guess, limit)
+    }
+
+    fn memo_guess(&self, rem_ans: &WordSet, rem_guess: &[Guess], limit: Option<Score>) -> Guess {
+        let rem_id = self.get_state_id(rem_ans, rem_guess, limit);
+        self.cache
+            .memo
+            .get_with(&rem_id, |(_, guess)| *guess)
+            .unwrap()
+    }
+
+    // memo から作られる決定木の宣言回数の合計.
+    fn memo_total(&self, rem_ans: &WordSet, rem_guess: &Vec<Guess>, limit: Option<Score>) -> Score {
+        if rem_ans.len() <= 2 {
+            return self.small_total(rem_ans);
+        }
+
+        let guess = self.memo_guess(rem_ans, rem_guess, limit);
+        let part = self.partition(rem_ans, &guess);
+
+        self.weight(rem_ans)
+            + part
+                .iter()
+                .map(|(judge, s)| {
+                    let next = self.next_guesses(rem_guess, &guess, judge);
+                    self.memo_total(s, next.as_ref().unwrap_or(rem_guess), limit.map(|d| d - 1))
+                })
+                .sum::<Score>()
+    }
+
+    fn dfs_build_guess_seq(
+        &self,
+        guess_seq: &mut Vec<Vec<Guess>>,
+        rem_ans: &WordSet,
+        rem_guess: &Vec<Guess>,
+        limit: Option<Score>,
+    ) {
+        if rem_ans.len() <= 2 {
+            let rem_ans = self.small_order(rem_ans);
+            guess_seq[rem_ans[0]].push(rem_ans[0]);
+            if rem_ans.len() == 1 {
+                return;
+            }
+            guess_seq[rem_ans[1]].push(rem_ans[0]);
+            guess_seq[rem_ans[1]].push(rem_ans[1]);
+            return;
+        }
+
+        let guess = self.memo_guess(rem_ans, rem_guess, limit);
+        let part = self.partition(rem_ans, &guess);
+
+        for ans in self.answers(rem_ans) {
+            guess_seq[ans].push(guess);
+        }
+        for (judge, s) in part.iter() {
+            let next = self.next_guesses(rem_guess, &guess, judge);
+            self.dfs_build_guess_seq(
+                guess_seq,
+                s,
+                next.as_ref().unwrap_or(rem_guess),
+                limit.map(|d| d - 1),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_budget_returns_greedy_tree() {
+        let words = WordList::default();
+        let greedy_total = SolverBuilder::new()
+            .ans_until(100)
+            .build(&words)
+            .unwrap()
+            .solve_greedy();
+
+        for builder in [
+            SolverBuilder::new().node_limit(0),
+            SolverBuilder::new().time_limit(Duration::ZERO),
+        ] {
+            let solver = builder.ans_until(100).build(&words).unwrap();
+            let solution = solver.solve();
+            assert!(solution.aborted);
+            assert_eq!(solution.total, greedy_total);
+            assert_eq!(solver.build_node().total() as Score, greedy_total);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_weights() {
+        let words = WordList::default();
+        let build = |weights: Vec<usize>| {
+            SolverBuilder::new()
+                .ans_until(100)
+                .weights(weights)
+                .build(&words)
+        };
+        assert!(build(vec![1; 99]).is_err());
+        let mut weights = vec![1; words.len()];
+        weights[0] = 0;
+        assert!(build(weights).is_err());
+        assert!(build(vec![1; words.len()]).is_ok());
+        assert!(build(vec![100_000_000; words.len()]).is_ok());
+        assert!(build(vec![1 << 56; words.len()]).is_err());
+    }
+
+    #[test]
+    fn worst_objective_minimizes_worst_case() {
+        let words = WordList::default();
+        let rg = words.until("RG").unwrap();
+        let solve = |objective| {
+            let solver = SolverBuilder::new()
+                .ans_until(rg)
+                .guess_until(rg)
+                .objective(objective)
+                .build(&words)
+                .unwrap();
+            solver.solve();
+            solver.build_node()
+        };
+        let average = solve(Objective::Average);
+        let worst = solve(Objective::Worst);
+        assert!(worst.worst() <= average.worst());
+        assert!(worst.total() >= average.total());
+    }
+
+    #[test]
+    fn hard_mode_tree_is_consistent() {
+        // 根からの (宣言, judge) の列に対して, 各宣言がそれまでの judge と矛盾しないことを確かめる.
+        fn check(node: &Node, judge_table: &JudgeTable, history: &mut Vec<(Guess, Judge)>) {
+            if let Node::NonTerminal { guess, edges, .. } = node {
+                assert!(judge_table.is_consistent(guess, history));
+                for (judge, child) in edges {
+                    history.push((*guess, *judge));
+                    check(child, judge_table, history);
+                    history.pop();
+                }
+            }
+        }
+
+        // GS まで宣言できれば, ハードモードでない決定木はこの確認を通らない.
+        let words = WordList::default();
+        let solver = SolverBuilder::new()
+            .ans_until(words.until("RG").unwrap())
+            .guess_until(words.until("GS").unwrap())
+            .hard(true)
+            .build(&words)
+            .unwrap();
+        solver.solve();
+        check(&solver.build_node(), &solver.judge_table, &mut vec![]);
+    }
+
+    #[test]
+    fn reject_out_of_range() {
+        let words = WordList::default();
+        let n = words.len();
+        assert!(SolverBuilder::new().ans_until(n + 1).build(&words).is_err());
+        assert!(SolverBuilder::new()
+            .guess_until(n + 1)
+            .build(&words)
+            .is_err());
+    }
+}
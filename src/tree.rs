@@ -1,10 +1,251 @@
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
-use std::io::Write;
+use std::io;
 use std::rc::Rc;
 
 use super::{judge::*, pokemon::*};
 
+// JSON で書き出す決定木の節点. スキーマは docs/tree.schema.json を参照.
+// 答えを当てた節点は全てのフィールドを省略した {} になる.
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+pub struct JsonNode {
+    // 宣言するポケモンの名前.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guess: Option<String>,
+    // 残りの答えの数. 答えに重みを付けた場合は重みの和.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rem: Option<usize>,
+    // この節点から答えを当てるまでの宣言回数の期待値.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    // 残りの答えの名前.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answers: Option<Vec<String>>,
+    // judge ("02110" の形) から次の節点への辺.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_edges"
+    )]
+    pub edges: BTreeMap<String, JsonNode>,
+}
+
+// 辺は文字列順ではなく Judge の値の順に書き出す. 1 文字目が最下位の桁なので, 逆から比べれば良い.
+fn serialize_edges<S: Serializer>(
+    edges: &BTreeMap<String, JsonNode>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut sorted: Vec<_> = edges.iter().collect();
+    sorted.sort_by(|(a, _), (b, _)| a.chars().rev().cmp(b.chars().rev()));
+    serializer.collect_map(sorted)
+}
+
+// コンパクトなバイナリ形式. 先頭から順に
+//   "WPT1", 文字数, 辞書の大きさ, 辞書の各名前 (バイト数と UTF-8),
+//   行きがけ順の各節点 (guess の辞書での番号, 辺の数 * 2 + 当たりの辺があれば 1,
+//   各辺について直前の辺との judge の差と子の節点)
+// を並べる. 整数は全て LEB128 の可変長で書く. rem は当たりの辺の数から復元する.
+const BINARY_MAGIC: &[u8] = b"WPT1";
+
+fn write_varint(out: &mut Vec<u8>, mut x: usize) {
+    while x >= 0x80 {
+        out.push((x & 0x7f) as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl BinaryReader<'_> {
+    fn truncated() -> TreeError {
+        TreeError::Malformed("the binary tree is truncated".to_string())
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&[u8], TreeError> {
+        let end = self.pos.checked_add(n).ok_or_else(Self::truncated)?;
+        let ret = self.bytes.get(self.pos..end).ok_or_else(Self::truncated)?;
+        self.pos = end;
+        Ok(ret)
+    }
+
+    fn read_varint(&mut self) -> Result<usize, TreeError> {
+        let mut ret = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let b = self.read_bytes(1)?[0];
+            ret |= ((b & 0x7f) as usize) << shift;
+            if b < 0x80 {
+                return Ok(ret);
+            }
+        }
+        Err(TreeError::Malformed("too large integer".to_string()))
+    }
+}
+
+impl JsonNode {
+    // rem, score, answers は書き出さない.
+    pub fn to_binary(&self) -> Result<Vec<u8>, TreeError> {
+        let word_len = self
+            .edges
+            .keys()
+            .next()
+            .map_or(0, |key| key.chars().count());
+        let mut dict: Vec<&str> = vec![];
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut body = vec![];
+        self.write_binary(&mut body, word_len, &mut dict, &mut index)?;
+
+        let mut out = BINARY_MAGIC.to_vec();
+        write_varint(&mut out, word_len);
+        write_varint(&mut out, dict.len());
+        for name in dict {
+            write_varint(&mut out, name.len());
+            out.extend_from_slice(name.as_bytes());
+        }
+        out.extend(body);
+        Ok(out)
+    }
+
+    fn write_binary<'a>(
+        &'a self,
+        out: &mut Vec<u8>,
+        word_len: usize,
+        dict: &mut Vec<&'a str>,
+        index: &mut HashMap<&'a str, usize>,
+    ) -> Result<(), TreeError> {
+        let name = self.guess.as_deref().ok_or(TreeError::Empty)?;
+        let id = *index.entry(name).or_insert_with(|| {
+            dict.push(name);
+            dict.len() - 1
+        });
+
+        let mut hit = false;
+        let mut edges: Vec<(Judge, &JsonNode)> = vec![];
+        for (key, ch) in self.edges.iter() {
+            let feedback =
+                Feedback::parse(key, word_len).map_err(|error| TreeError::InvalidFeedback {
+                    key: key.clone(),
+                    error,
+                })?;
+            if ch.guess.is_some() {
+                edges.push((feedback.to_judge(), ch));
+            } else if feedback.0.iter().all(|status| *status == Status::Correct) {
+                hit = true;
+            } else {
+                return Err(TreeError::Malformed(format!(
+                    "the node after {} for \"{}\" has no guess",
+                    name, key
+                )));
+            }
+        }
+        edges.sort_by_key(|(judge, _)| *judge);
+
+        write_varint(out, id);
+        write_varint(out, edges.len() * 2 + hit as usize);
+        let mut prev = 0;
+        for (judge, ch) in edges {
+            write_varint(out, judge - prev);
+            prev = judge;
+            ch.write_binary(out, word_len, dict, index)?;
+        }
+        Ok(())
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, TreeError> {
+        let mut reader = BinaryReader { bytes, pos: 0 };
+        if reader.read_bytes(BINARY_MAGIC.len())? != BINARY_MAGIC {
+            return Err(TreeError::Malformed("not a binary tree".to_string()));
+        }
+        let word_len = reader.read_varint()?;
+        if word_len == 0 || word_len > MAX_WORD_LEN {
+            return Err(TreeError::Malformed(format!(
+                "invalid word length {}",
+                word_len
+            )));
+        }
+        let dict = (0..reader.read_varint()?)
+            .map(|_| {
+                let len = reader.read_varint()?;
+                String::from_utf8(reader.read_bytes(len)?.to_vec())
+                    .map_err(|_| TreeError::Malformed("invalid UTF-8 name".to_string()))
+            })
+            .collect::<Result<Vec<String>, TreeError>>()?;
+
+        let root = Self::read_binary(&mut reader, &dict, word_len)?;
+        if reader.pos != bytes.len() {
+            return Err(TreeError::Malformed(
+                "trailing bytes after the binary tree".to_string(),
+            ));
+        }
+        Ok(root)
+    }
+
+    fn read_binary(
+        reader: &mut BinaryReader,
+        dict: &[String],
+        word_len: usize,
+    ) -> Result<Self, TreeError> {
+        let id = reader.read_varint()?;
+        let name = dict
+            .get(id)
+            .ok_or_else(|| TreeError::Malformed(format!("unknown name index {}", id)))?;
+        let flags = reader.read_varint()?;
+        let key = |judge| {
+            Feedback::from_judge(judge, word_len)
+                .map(|feedback| feedback.to_string())
+                .map_err(|e| TreeError::Malformed(e.to_string()))
+        };
+
+        let mut edges = BTreeMap::new();
+        let mut rem = flags & 1;
+        if flags & 1 == 1 {
+            edges.insert(key(all_correct(word_len))?, JsonNode::default());
+        }
+        // 辺は judge の昇順に並ぶので, 2 本目以降の差分が 0 なら同じ judge の辺が重複している.
+        // 全て正解の辺は flags の最下位ビットで表すので, 差分で書かれることはない.
+        let mut judge: Judge = 0;
+        for i in 0..flags / 2 {
+            let delta = reader.read_varint()?;
+            if i > 0 && delta == 0 {
+                return Err(TreeError::Malformed(format!(
+                    "duplicate edge for judge {} under {}",
+                    key(judge)?,
+                    name
+                )));
+            }
+            judge = judge
+                .checked_add(delta)
+                .ok_or_else(|| TreeError::Malformed("too large judge".to_string()))?;
+            if judge == all_correct(word_len) {
+                return Err(TreeError::Malformed(format!(
+                    "the all-correct edge under {} is written as a judge",
+                    name
+                )));
+            }
+            let ch = Self::read_binary(reader, dict, word_len)?;
+            rem += ch.rem.unwrap_or(0);
+            edges.insert(key(judge)?, ch);
+        }
+
+        Ok(JsonNode {
+            guess: Some(name.clone()),
+            rem: Some(rem),
+            edges,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct JsonOptions {
+    pub answers: bool,
+    pub score: bool,
+}
+
 pub enum Node {
     Terminal,
     NonTerminal {
@@ -15,104 +256,696 @@ pub enum Node {
 }
 
 impl Node {
-    pub fn write(&self, out: &mut fs::File) {
+    pub fn write(&self, out: &mut fs::File, pokemons: &PokemonList, options: &JsonOptions) {
+        serde_json::to_writer(out, &self.to_json(pokemons, options)).unwrap();
+    }
+
+    pub fn to_json(&self, pokemons: &PokemonList, options: &JsonOptions) -> JsonNode {
         match self {
             Node::NonTerminal {
                 guess,
                 edges,
                 rem_ans,
             } => {
-                out.write_all(
-                    format!(
-                        "{{\"guess\":\"{}\",\"rem\":{},\"edges\":{{",
-                        POKEMONS[*guess],
-                        rem_ans.len(),
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-                for (i, (judge, ch)) in edges.iter().enumerate() {
-                    let judge = (0..5)
-                        .map(|i| (judge >> 2 * i & 0b11).to_string())
-                        .collect::<Vec<String>>()
-                        .join("");
-                    out.write_all(format!("\"{}\":", judge).as_bytes()).unwrap();
-                    ch.write(out);
-                    if i + 1 < edges.len() {
-                        out.write_all(",".as_bytes()).unwrap();
-                    }
+                let rem: usize = rem_ans.iter().map(|ans| pokemons.weight(*ans)).sum();
+                JsonNode {
+                    guess: Some(pokemons.names[*guess].clone()),
+                    rem: Some(rem),
+                    score: if options.score {
+                        Some(self.weighted_total(pokemons) as f64 / rem as f64)
+                    } else {
+                        None
+                    },
+                    answers: if options.answers {
+                        Some(
+                            rem_ans
+                                .iter()
+                                .map(|ans| pokemons.names[*ans].clone())
+                                .collect(),
+                        )
+                    } else {
+                        None
+                    },
+                    edges: edges
+                        .iter()
+                        .map(|(judge, ch)| {
+                            (
+                                Feedback::from_judge(*judge, pokemons.word_len)
+                                    .expect("judges in the tree come from the judge table")
+                                    .to_string(),
+                                ch.to_json(pokemons, options),
+                            )
+                        })
+                        .collect(),
                 }
-                out.write_all("}}".as_bytes()).unwrap();
             }
-            Node::Terminal => {
-                out.write_all("{}".as_bytes()).unwrap();
+            Node::Terminal => JsonNode::default(),
+        }
+    }
+
+    // 残りの答えそれぞれについて, この節点から当てるまでの宣言回数の合計.
+    pub fn total(&self) -> usize {
+        match self {
+            Node::NonTerminal { edges, rem_ans, .. } => {
+                rem_ans.len() + edges.values().map(|ch| ch.total()).sum::<usize>()
+            }
+            Node::Terminal => 0,
+        }
+    }
+
+    // 残りの答えのうち, この節点から当てるまでの宣言回数の最大値.
+    pub fn worst(&self) -> usize {
+        match self {
+            Node::NonTerminal { edges, .. } => {
+                1 + edges.values().map(|ch| ch.worst()).max().unwrap_or(0)
             }
+            Node::Terminal => 0,
+        }
+    }
+
+    // total の各答えに pokemons の重みを掛けたもの.
+    pub fn weighted_total(&self, pokemons: &PokemonList) -> usize {
+        match self {
+            Node::NonTerminal { edges, rem_ans, .. } => {
+                rem_ans
+                    .iter()
+                    .map(|ans| pokemons.weight(*ans))
+                    .sum::<usize>()
+                    + edges
+                        .values()
+                        .map(|ch| ch.weighted_total(pokemons))
+                        .sum::<usize>()
+            }
+            Node::Terminal => 0,
+        }
+    }
+
+    pub fn next(&self, judge: &Judge) -> Option<Rc<Node>> {
+        match self {
+            Node::NonTerminal { edges, .. } => edges.get(judge).map(Rc::clone),
+            Node::Terminal => None,
+        }
+    }
+
+    // この節点で返りうる judge.
+    pub fn judges(&self) -> Vec<Judge> {
+        match self {
+            Node::NonTerminal { edges, .. } => edges.keys().copied().collect(),
+            Node::Terminal => vec![],
         }
     }
-    pub fn next(&self, judge: &Judge) -> Rc<Node> {
-        if let Node::NonTerminal { edges, .. } = self {
-            return Rc::clone(&edges[judge]);
+}
+
+// 行番号は 1 始まり. 答えの番号 i の宣言列はファイルの i + 1 行目にある.
+#[derive(Debug)]
+pub enum TreeError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Empty,
+    UnknownName(String),
+    InvalidFeedback {
+        key: String,
+        error: FeedbackError,
+    },
+    Malformed(String),
+    Parse {
+        line: usize,
+        token: String,
+    },
+    UnknownPokemon {
+        line: usize,
+        pokemon: Pokemon,
+    },
+    AnswerCount {
+        expected: usize,
+        found: usize,
+    },
+    GuessCount {
+        expected: usize,
+        found: usize,
+    },
+    Truncated {
+        answer: Answer,
+        depth: usize,
+    },
+    Inconsistent {
+        answer: Answer,
+        depth: usize,
+        expected: Guess,
+        found: Guess,
+    },
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::Io(e) => write!(f, "failed to read the decision tree: {}", e),
+            TreeError::Json(e) => write!(f, "invalid JSON: {}", e),
+            TreeError::Empty => write!(f, "the decision tree has no answer pokemons"),
+            TreeError::UnknownName(name) => write!(f, "unknown pokemon: {}", name),
+            TreeError::InvalidFeedback { key, error } => {
+                write!(f, "invalid judge \"{}\": {}", key, error)
+            }
+            TreeError::Malformed(msg) => write!(f, "{}", msg),
+            TreeError::Parse { line, token } => {
+                write!(f, "line {}: invalid pokemon number '{}'", line, token)
+            }
+            TreeError::UnknownPokemon { line, pokemon } => {
+                write!(f, "line {}: pokemon {} cannot be guessed", line, pokemon)
+            }
+            TreeError::AnswerCount { expected, found } => write!(
+                f,
+                "expected {} lines (one per answer pokemon), found {}",
+                expected, found
+            ),
+            TreeError::GuessCount { expected, found } => write!(
+                f,
+                "the tree guesses pokemon {}, but only {} pokemons can be guessed",
+                found - 1,
+                expected
+            ),
+            TreeError::Truncated { answer, depth } => write!(
+                f,
+                "line {}: the guess sequence ends at guess {} before reaching the answer",
+                answer + 1,
+                depth + 1
+            ),
+            TreeError::Inconsistent {
+                answer,
+                depth,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: guess {} is {}, but {} for the other answers with the same judges",
+                answer + 1,
+                depth + 1,
+                found,
+                expected
+            ),
         }
-        panic!("Incorrect judge.");
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+impl From<io::Error> for TreeError {
+    fn from(e: io::Error) -> Self {
+        TreeError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TreeError {
+    fn from(e: serde_json::Error) -> Self {
+        TreeError::Json(e)
     }
 }
 
 #[derive(Default)]
 pub struct DecisionTree {
+    // 宣言列のテキストから読み込んだ場合の, 答えごとの宣言列.
     pub guess_seq: Vec<Vec<Guess>>,
+    // JSON やバイナリから読み込んだ場合の根.
+    root: Option<Rc<Node>>,
+    pub pokemons: PokemonList,
     pub judge_table: JudgeTable,
 }
 
 impl DecisionTree {
-    pub fn new(filepath: &str) -> Self {
-        let guess_seq: Vec<Vec<Guess>> = fs::read_to_string(filepath)
-            .unwrap()
+    // 拡張子が .json なら JSON, .bin ならバイナリ, それ以外は宣言列のテキストとして読み込む.
+    pub fn load(
+        filepath: &str,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<Self, TreeError> {
+        if filepath.ends_with(".json") {
+            Self::from_json(filepath, words, ans_until, guess_until, answers)
+        } else if filepath.ends_with(".bin") {
+            let root = JsonNode::from_binary(&fs::read(filepath)?)?;
+            Self::from_json_node(&root, words, ans_until, guess_until, answers)
+        } else {
+            Self::new(filepath, words, ans_until, guess_until, answers)
+        }
+    }
+
+    // load して根も作る. エラーにはファイル名を付ける.
+    pub fn load_root(
+        filepath: &str,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<(Self, Rc<Node>), String> {
+        Self::load(filepath, words, ans_until, guess_until, answers)
+            .and_then(|tree| {
+                let root = tree.root()?;
+                Ok((tree, root))
+            })
+            .map_err(|e| format!("{}: {}", filepath, e))
+    }
+
+    // ans_until, guess_until が None なら決定木から推定する.
+    pub fn new(
+        filepath: &str,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<Self, TreeError> {
+        let guess_seq: Vec<Vec<Guess>> = fs::read_to_string(filepath)?
             .lines()
-            .map(|line| {
+            .enumerate()
+            .map(|(i, line)| {
                 line.split_whitespace()
-                    .map(|s| s.parse::<Guess>().unwrap())
+                    .map(|s| {
+                        let guess = s.parse::<Guess>().map_err(|_| TreeError::Parse {
+                            line: i + 1,
+                            token: s.to_string(),
+                        })?;
+                        if guess >= words.len() {
+                            return Err(TreeError::UnknownPokemon {
+                                line: i + 1,
+                                pokemon: guess,
+                            });
+                        }
+                        Ok(guess)
+                    })
                     .collect()
             })
+            .collect::<Result<_, _>>()?;
+
+        Self::from_guess_seq(guess_seq, words, ans_until, guess_until, answers)
+    }
+
+    pub fn from_json(
+        filepath: &str,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<Self, TreeError> {
+        let root: JsonNode = serde_json::from_str(&fs::read_to_string(filepath)?)?;
+        Self::from_json_node(&root, words, ans_until, guess_until, answers)
+    }
+
+    // 辺の judge は判定表と照らし合わせ, 辺の抜けや余分な辺があればエラーにする.
+    pub fn from_json_node(
+        root: &JsonNode,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<Self, TreeError> {
+        let index: HashMap<&str, Pokemon> = words
+            .names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
             .collect();
 
-        let ans_until = guess_seq.len();
-        let guess_until = *guess_seq
+        // 答えの文字数は, 根の辺の judge の文字数.
+        let word_len = root
+            .edges
+            .keys()
+            .next()
+            .map_or(WORD_LEN, |key| key.chars().count());
+
+        let mut hits: Vec<Answer> = vec![];
+        let mut max_guess = 0;
+        Self::scan_json(root, &index, word_len, &mut hits, &mut max_guess)?;
+
+        let min_ans_until = hits.iter().max().map_or(0, |ans| ans + 1);
+        let ans_until = ans_until.unwrap_or(min_ans_until);
+        if ans_until < min_ans_until || ans_until > words.len() {
+            return Err(TreeError::AnswerCount {
+                expected: ans_until.min(words.len()),
+                found: min_ans_until,
+            });
+        }
+        let guess_until = guess_until.unwrap_or(max_guess + 1);
+        if guess_until <= max_guess || guess_until > words.len() {
+            return Err(TreeError::GuessCount {
+                expected: guess_until.min(words.len()),
+                found: max_guess + 1,
+            });
+        }
+
+        let mut pokemons = PokemonList::from_words(words, word_len, ans_until, guess_until)
+            .map_err(TreeError::Malformed)?;
+        Self::restrict_answers(&mut pokemons, words, answers)?;
+        let judge_table = JudgeTable::new(&pokemons);
+
+        let mut tree = Self {
+            pokemons,
+            judge_table,
+            ..Default::default()
+        };
+        tree.root = Some(tree.build_json(root, &index, &tree.pokemons.all_ans)?);
+        Ok(tree)
+    }
+
+    // 決定木を一巡りして, 当てた答えと宣言の番号の最大値を集める.
+    fn scan_json(
+        node: &JsonNode,
+        index: &HashMap<&str, Pokemon>,
+        word_len: usize,
+        hits: &mut Vec<Answer>,
+        max_guess: &mut Guess,
+    ) -> Result<(), TreeError> {
+        let name = node.guess.as_ref().ok_or(TreeError::Empty)?;
+        let guess = *index
+            .get(name.as_str())
+            .ok_or_else(|| TreeError::UnknownName(name.clone()))?;
+        *max_guess = guess.max(*max_guess);
+
+        for (key, ch) in node.edges.iter() {
+            let feedback =
+                Feedback::parse(key, word_len).map_err(|error| TreeError::InvalidFeedback {
+                    key: key.clone(),
+                    error,
+                })?;
+            if feedback.to_judge() == all_correct(word_len) {
+                if ch.guess.is_some() {
+                    return Err(TreeError::Malformed(format!(
+                        "the node after {} for \"{}\" must be empty",
+                        name, key
+                    )));
+                }
+                hits.push(guess);
+            } else if ch.guess.is_some() {
+                Self::scan_json(ch, index, word_len, hits, max_guess)?;
+            } else {
+                return Err(TreeError::Malformed(format!(
+                    "the node after {} for \"{}\" has no guess",
+                    name, key
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // scan_json で辺の judge と節点の形は確かめてある.
+    fn build_json(
+        &self,
+        node: &JsonNode,
+        index: &HashMap<&str, Pokemon>,
+        rem_ans: &[Answer],
+    ) -> Result<Rc<Node>, TreeError> {
+        let names = &self.pokemons.names;
+        let name = node.guess.as_ref().ok_or(TreeError::Empty)?;
+        let guess = index[name.as_str()];
+        if !self.pokemons.is_valid_guess[guess] {
+            return Err(TreeError::Malformed(format!("{} cannot be guessed", name)));
+        }
+
+        let mut partition = self.judge_table.partition(rem_ans, &guess);
+        let mut edges: BTreeMap<Judge, Rc<Node>> = BTreeMap::new();
+        for (key, ch) in node.edges.iter() {
+            let judge = Feedback::parse(key, self.pokemons.word_len)
+                .map_err(|error| TreeError::InvalidFeedback {
+                    key: key.clone(),
+                    error,
+                })?
+                .to_judge();
+            if judge == self.judge_table.all_correct {
+                if !rem_ans.contains(&guess) {
+                    return Err(TreeError::Malformed(format!(
+                        "{} is guessed as an answer, but it is not one of the remaining answers",
+                        name
+                    )));
+                }
+                edges.insert(judge, Rc::new(Node::Terminal));
+            } else {
+                let s = partition.remove(&judge).ok_or_else(|| {
+                    TreeError::Malformed(format!(
+                        "no remaining answer gives \"{}\" for {}",
+                        key, name
+                    ))
+                })?;
+                edges.insert(judge, self.build_json(ch, index, &s)?);
+            }
+        }
+
+        // 辺のない judge が残っていれば, その答えには辿り着けない.
+        if let Some((judge, s)) = partition.iter().min_by_key(|(judge, _)| **judge) {
+            return Err(TreeError::Malformed(format!(
+                "{} is never reached: {} has no edge for \"{}\"",
+                names[s[0]],
+                name,
+                Feedback::from_judge(*judge, self.pokemons.word_len)
+                    .expect("judges in the partition come from the judge table")
+            )));
+        }
+        if rem_ans.contains(&guess) && !edges.contains_key(&self.judge_table.all_correct) {
+            return Err(TreeError::Malformed(format!(
+                "{} is never reached: it is guessed but not marked as the answer",
+                name
+            )));
+        }
+
+        Ok(Rc::new(Node::NonTerminal {
+            guess,
+            edges,
+            rem_ans: rem_ans.to_vec(),
+        }))
+    }
+
+    fn from_guess_seq(
+        guess_seq: Vec<Vec<Guess>>,
+        words: &WordList,
+        ans_until: Option<usize>,
+        guess_until: Option<usize>,
+        answers: &WordFilter,
+    ) -> Result<Self, TreeError> {
+        let ans_until = ans_until.unwrap_or(guess_seq.len());
+        if ans_until != guess_seq.len() || ans_until > words.len() {
+            return Err(TreeError::AnswerCount {
+                expected: ans_until.min(words.len()),
+                found: guess_seq.len(),
+            });
+        }
+        let min_guess_until = *guess_seq
             .iter()
             .map(|seq| seq.iter().max().unwrap_or(&0))
             .max()
-            .unwrap()
+            .unwrap_or(&0)
             + 1;
+        let guess_until = guess_until.unwrap_or(min_guess_until);
+        if guess_until < min_guess_until {
+            return Err(TreeError::GuessCount {
+                expected: guess_until,
+                found: min_guess_until,
+            });
+        }
+
+        // 答えの文字数は, 最後に宣言されるポケモンの文字数.
+        let word_len = guess_seq
+            .iter()
+            .find_map(|seq| seq.last())
+            .map_or(WORD_LEN, |ans| words.names[*ans].chars().count());
 
-        Self {
+        let mut pokemons = PokemonList::from_words(words, word_len, ans_until, guess_until)
+            .map_err(TreeError::Malformed)?;
+        Self::restrict_answers(&mut pokemons, words, answers)?;
+        for (i, seq) in guess_seq.iter().enumerate() {
+            if let Some(guess) = seq.iter().find(|guess| !pokemons.is_valid_guess[**guess]) {
+                return Err(TreeError::UnknownPokemon {
+                    line: i + 1,
+                    pokemon: *guess,
+                });
+            }
+        }
+        let judge_table = JudgeTable::new(&pokemons);
+
+        Ok(Self {
             guess_seq,
-            judge_table: JudgeTable::new(ans_until, guess_until),
+            pokemons,
+            judge_table,
+            ..Default::default()
+        })
+    }
+
+    // 答えは ans_until までの全てのポケモンで, 決定木は全ての答えを当てなければならない.
+    // --allow-answers や --deny-answers で解いた決定木は, 同じ絞り込みを answers で与えて読む.
+    fn restrict_answers(
+        pokemons: &mut PokemonList,
+        words: &WordList,
+        answers: &WordFilter,
+    ) -> Result<(), TreeError> {
+        if pokemons.all_ans.is_empty() {
+            return Err(TreeError::Empty);
+        }
+        pokemons
+            .restrict(words, answers, &WordFilter::default())
+            .map_err(TreeError::Malformed)
+    }
+
+    // 全ての答えについての決定木の根.
+    pub fn root(&self) -> Result<Rc<Node>, TreeError> {
+        match &self.root {
+            Some(root) => Ok(Rc::clone(root)),
+            None => self.build(&self.pokemons.all_ans, 0),
         }
     }
 
-    pub fn build(&self, rem_ans: &Vec<Answer>, depth: usize) -> Rc<Node> {
-        assert!(rem_ans.len() > 0);
+    pub fn build(&self, rem_ans: &[Answer], depth: usize) -> Result<Rc<Node>, TreeError> {
+        assert!(!rem_ans.is_empty());
+
+        let guess_at = |ans: &Answer| {
+            self.guess_seq[*ans]
+                .get(depth)
+                .copied()
+                .ok_or(TreeError::Truncated {
+                    answer: *ans,
+                    depth,
+                })
+        };
 
-        let guess = self.guess_seq[rem_ans[0]][depth];
-        for i in 1..rem_ans.len() {
-            assert!(self.guess_seq[rem_ans[i]].len() > depth);
-            assert!(guess == self.guess_seq[rem_ans[i]][depth]);
+        let guess = guess_at(&rem_ans[0])?;
+        for ans in rem_ans.iter().skip(1) {
+            let found = guess_at(ans)?;
+            if found != guess {
+                return Err(TreeError::Inconsistent {
+                    answer: *ans,
+                    depth,
+                    expected: guess,
+                    found,
+                });
+            }
         }
 
         let mut edges: BTreeMap<Judge, Rc<Node>> = self
             .judge_table
             .partition(rem_ans, &guess)
             .iter()
-            .map(|(judge, s)| (*judge, self.build(s, depth + 1)))
-            .collect();
+            .map(|(judge, s)| Ok((*judge, self.build(s, depth + 1)?)))
+            .collect::<Result<_, TreeError>>()?;
 
         if rem_ans.contains(&guess) {
-            edges.insert(ALL_CORRECT, Rc::new(Node::Terminal));
+            edges.insert(self.judge_table.all_correct, Rc::new(Node::Terminal));
         }
 
-        return Rc::new(Node::NonTerminal {
+        Ok(Rc::new(Node::NonTerminal {
             guess,
             edges,
-            rem_ans: rem_ans.clone(),
-        });
+            rem_ans: rem_ans.to_vec(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opt_dp() -> JsonNode {
+        serde_json::from_str(include_str!("../outputs/opt_DP.json")).unwrap()
+    }
+
+    // バイナリには score を書き出さないので, 比べる前に消しておく.
+    fn without_score(mut node: JsonNode) -> JsonNode {
+        node.score = None;
+        node.edges = node
+            .edges
+            .into_iter()
+            .map(|(key, ch)| (key, without_score(ch)))
+            .collect();
+        node
+    }
+
+    // "WPT1", 5 文字, 辞書 ["a"], a から judge の差 delta の辺で a を当てる決定木.
+    fn tiny_binary(delta: u8) -> Vec<u8> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[5, 1, 1, b'a', 0, 2, delta, 0, 1]);
+        bytes
+    }
+
+    #[test]
+    fn reject_unreached_answer() {
+        let words = WordList::default();
+        let load = |root: &JsonNode, answers: &WordFilter| {
+            DecisionTree::from_json_node(root, &words, None, None, answers)
+        };
+
+        let mut root = opt_dp();
+        assert!(load(&root, &WordFilter::default()).is_ok());
+
+        // 2 回目に当たる答えの辺を消すと, その答えには辿り着けない.
+        let node = root
+            .edges
+            .values_mut()
+            .find(|ch| ch.edges.values().any(|ch| ch.guess.is_none()))
+            .unwrap();
+        node.edges.retain(|_, ch| ch.guess.is_some());
+        let name = node.guess.clone().unwrap();
+        assert!(load(&root, &WordFilter::default()).is_err());
+
+        // その答えを除けば読める.
+        let answers = WordFilter {
+            allow: None,
+            deny: words
+                .names
+                .iter()
+                .position(|s| *s == name)
+                .into_iter()
+                .collect(),
+        };
+        assert!(load(&root, &answers).is_ok());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let json = opt_dp();
+        let bytes = json.to_binary().unwrap();
+        let decoded = JsonNode::from_binary(&bytes).unwrap();
+        assert!(decoded == without_score(json));
+        assert_eq!(decoded.to_binary().unwrap(), bytes);
+    }
+
+    #[test]
+    fn reject_truncated_binary() {
+        let bytes = opt_dp().to_binary().unwrap();
+        for len in 0..bytes.len() {
+            assert!(JsonNode::from_binary(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_corrupt_binary() {
+        assert!(JsonNode::from_binary(&tiny_binary(1)).is_ok());
+
+        let mut bytes = tiny_binary(1);
+        bytes[0] = b'X';
+        assert!(JsonNode::from_binary(&bytes).is_err());
+
+        // 文字数 0
+        let mut bytes = tiny_binary(1);
+        bytes[4] = 0;
+        assert!(JsonNode::from_binary(&bytes).is_err());
+
+        // 辞書にない名前の番号
+        let mut bytes = tiny_binary(1);
+        bytes[8] = 3;
+        assert!(JsonNode::from_binary(&bytes).is_err());
+
+        // 1 文字目が 3 の judge
+        assert!(JsonNode::from_binary(&tiny_binary(3)).is_err());
+
+        // 2 本目の辺の差分が 0 (同じ judge の辺が 2 本)
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[5, 1, 1, b'a', 0, 4, 1, 0, 1, 0, 0, 1]);
+        assert!(JsonNode::from_binary(&bytes).is_err());
+        bytes[13] = 1;
+        assert!(JsonNode::from_binary(&bytes).is_ok());
+
+        // 1 文字で全て正解の judge を, 当たりのビットでなく辺として書いたもの
+        let mut bytes = tiny_binary(all_correct(1) as u8);
+        bytes[4] = 1;
+        assert!(JsonNode::from_binary(&bytes).is_err());
+
+        let mut bytes = tiny_binary(1);
+        bytes.push(0);
+        assert!(JsonNode::from_binary(&bytes).is_err());
     }
 }
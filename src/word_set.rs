@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+const BLOCKS: usize = 16;
+
+// 番号の集合. 番号は CAPACITY 未満でなければならない.
+// Solver では単語の番号ではなく, 答え (ハードモードでは宣言も) の一覧での位置を入れる.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordSet([u64; BLOCKS]);
+
+impl WordSet {
+    pub const CAPACITY: usize = 64 * BLOCKS;
+
+    pub fn from_slice(words: &[usize]) -> Self {
+        let mut ret = Self::default();
+        for word in words {
+            ret.insert(*word);
+        }
+        ret
+    }
+
+    pub fn insert(&mut self, word: usize) {
+        assert!(word < Self::CAPACITY, "word index {} is too large", word);
+        self.0[word / 64] |= 1 << (word % 64);
+    }
+
+    pub fn contains(&self, word: usize) -> bool {
+        word < Self::CAPACITY && (self.0[word / 64] >> (word % 64) & 1) > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, b)| {
+            let mut b = *b;
+            std::iter::from_fn(move || {
+                if b == 0 {
+                    return None;
+                }
+                let j = b.trailing_zeros() as usize;
+                b &= b - 1;
+                Some(64 * i + j)
+            })
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<usize> {
+        self.iter().collect()
+    }
+}
+
+// 128 バイトをそのまま SipHash に通すと遅いので, 1 つの u64 に畳み込んでから渡す.
+impl Hash for WordSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let h = self.0.iter().fold(0u64, |h, b| {
+            (h.rotate_left(5) ^ b).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95)
+        });
+        state.write_u64(h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_across_blocks() {
+        let words = [
+            0,
+            1,
+            62,
+            63,
+            64,
+            65,
+            127,
+            128,
+            511,
+            512,
+            WordSet::CAPACITY - 1,
+        ];
+        let mut st = WordSet::default();
+        assert!(st.is_empty());
+        for (n, word) in words.iter().enumerate() {
+            assert!(!st.contains(*word));
+            st.insert(*word);
+            st.insert(*word);
+            assert!(st.contains(*word));
+            assert_eq!(st.len(), n + 1);
+        }
+        assert_eq!(st.to_vec(), words);
+        assert!(st == WordSet::from_slice(&words));
+        for word in [2, 61, 66, 126, 129, 1000] {
+            assert!(!st.contains(word));
+        }
+        assert!(!st.contains(WordSet::CAPACITY));
+    }
+
+    #[test]
+    fn iter_full_blocks() {
+        let words: Vec<usize> = (60..200).collect();
+        let st = WordSet::from_slice(&words);
+        assert_eq!(st.len(), words.len());
+        assert_eq!(st.to_vec(), words);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_out_of_capacity() {
+        WordSet::default().insert(WordSet::CAPACITY);
+    }
+}